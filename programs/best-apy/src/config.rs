@@ -0,0 +1,62 @@
+use anchor_lang::prelude::*;
+
+pub const CONFIG_VERSION: u8 = 1;
+
+/// Global program configuration account
+#[account]
+#[derive(Default)]
+pub struct ConfigAccount {
+    /// Config version
+    pub version: u8,
+    /// PDA bump seed
+    pub bump: u8,
+
+    /// Authority managing vaults, protocols, hashes, fees and roles
+    pub admin: Pubkey,
+    /// Proposed admin which has to accept the authority transfer
+    pub pending_admin: Pubkey,
+    /// Authority allowed to pause the vaults
+    pub guardian: Pubkey,
+    /// Authority allowed to set the weights and refresh parameters
+    pub strategist: Pubkey,
+
+    /// Additional padding
+    pub _padding: [u64; 8],
+}
+
+impl ConfigAccount {
+    pub const SIZE: usize = 1 + 1 + 32 + 32 + 32 + 32 + 8 * 8;
+
+    /// Initialize the config with every role assigned to the given authority
+    pub fn init(bump: u8, authority: Pubkey) -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            bump,
+            admin: authority,
+            guardian: authority,
+            strategist: authority,
+            ..Self::default()
+        }
+    }
+
+    /// Check if the key holds the given role. The admin holds every role
+    pub fn has_role(&self, key: &Pubkey, role: Role) -> bool {
+        if key == &self.admin {
+            return true;
+        }
+
+        match role {
+            Role::Admin => false,
+            Role::Guardian => key == &self.guardian,
+            Role::Strategist => key == &self.strategist,
+        }
+    }
+}
+
+/// Authorities allowed to operate the vaults
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Admin,
+    Guardian,
+    Strategist,
+}
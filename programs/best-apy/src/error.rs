@@ -21,7 +21,7 @@ pub enum ErrorCode {
     InvalidOwner,
     #[msg("Invalid mint")]
     InvalidMint,
-    #[msg("Deposits and withdraws aren't allowed on PAUSE")]
+    #[msg("This operation is paused in the vault")]
     OnPaused,
     #[msg("The instructions provided are invalid")]
     InvalidInstructions,
@@ -47,4 +47,32 @@ pub enum ErrorCode {
     InvalidProtocolId,
     #[msg("Invalid ticket amount")]
     InvalidTicketAmount,
+    #[msg("Fee exceeds its maximum value")]
+    InvalidFee,
+    #[msg("Slippage tolerance exceeded")]
+    SlippageExceeded,
+    #[msg("Vault already exists in the index")]
+    VaultAlreadyIndexed,
+    #[msg("Vault already exists in the registry")]
+    VaultAlreadyRegistered,
+    #[msg("Vault not found in the registry")]
+    VaultNotRegistered,
+    #[msg("Maximum number of vaults in the registry reached")]
+    RegistryFull,
+    #[msg("Invalid loss amount")]
+    InvalidLossAmount,
+    #[msg("Protocol accounts of a different vault")]
+    InvalidVaultAccount,
+    #[msg("Withdrawal epoch no longer accepts tickets")]
+    WithdrawEpochClosed,
+    #[msg("Withdrawal epoch still accepts tickets")]
+    WithdrawEpochOpen,
+    #[msg("Withdrawal ticket opened in a previous epoch must be closed first")]
+    TicketInPreviousEpoch,
+    #[msg("Not enough idle liquidity besides the funds reserved for the withdrawal tickets")]
+    InsufficientLiquidity,
+    #[msg("Invalid maximum reserve share")]
+    InvalidReserveShare,
+    #[msg("Invalid rebalance damping parameters")]
+    InvalidDampingParams,
 }
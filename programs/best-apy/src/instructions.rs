@@ -1,34 +1,90 @@
+pub mod accept_admin;
 pub mod add_protocol;
+pub mod cancel_withdraw_ticket;
 pub mod close_withdraw_ticket;
+pub mod close_withdraw_ticket_accounts;
+pub mod close_withdraw_ticket_to_native;
 pub mod create_vault_user_ticket_account;
+pub mod delist_vault;
 pub mod deposit;
 pub mod deposit_from_native;
+pub mod fill_withdraw_epoch;
+pub mod initialize_config;
 pub mod initialize_ticket_mint;
 pub mod initialize_vault;
 pub mod mango_reimbursement;
+pub mod migrate_withdraw_ticket;
 pub mod open_withdraw_ticket;
+pub mod propose_admin;
+pub mod rebalance;
+pub mod recognize_loss;
+pub mod refresh_all_tvl;
 pub mod refresh_weights;
+pub mod register_vault;
+pub mod set_damping_params;
+pub mod set_fees;
 pub mod set_hashes;
+pub mod set_idle_weight;
+pub mod set_max_reserve_share;
+pub mod set_pause_flags;
+pub mod set_protocol_caps;
+pub mod set_protocol_risk_score;
 pub mod set_protocol_weights;
 pub mod set_refresh_params;
+pub mod set_roles;
+pub mod set_settle_bounty;
+pub mod set_treasury;
+pub mod set_weight_strategy;
+pub mod settle_withdraw_ticket;
+pub mod sync_idle_balance;
 pub mod withdraw;
 pub mod withdraw_and_close;
+pub mod withdraw_exact_tokens;
+pub mod withdraw_to_native;
 
+pub use accept_admin::*;
 pub use add_protocol::*;
+pub use cancel_withdraw_ticket::*;
 pub use close_withdraw_ticket::*;
+pub use close_withdraw_ticket_accounts::*;
+pub use close_withdraw_ticket_to_native::*;
 pub use create_vault_user_ticket_account::*;
+pub use delist_vault::*;
 pub use deposit::*;
 pub use deposit_from_native::*;
+pub use fill_withdraw_epoch::*;
+pub use initialize_config::*;
 pub use initialize_ticket_mint::*;
 pub use initialize_vault::*;
 pub use mango_reimbursement::*;
+pub use migrate_withdraw_ticket::*;
 pub use open_withdraw_ticket::*;
+pub use propose_admin::*;
+pub use rebalance::*;
+pub use recognize_loss::*;
+pub use refresh_all_tvl::*;
 pub use refresh_weights::*;
+pub use register_vault::*;
+pub use set_damping_params::*;
+pub use set_fees::*;
 pub use set_hashes::*;
+pub use set_idle_weight::*;
+pub use set_max_reserve_share::*;
+pub use set_pause_flags::*;
+pub use set_protocol_caps::*;
+pub use set_protocol_risk_score::*;
 pub use set_protocol_weights::*;
 pub use set_refresh_params::*;
+pub use set_roles::*;
+pub use set_settle_bounty::*;
+pub use set_treasury::*;
+pub use set_weight_strategy::*;
+pub use settle_withdraw_ticket::*;
+pub use sync_idle_balance::*;
 pub use withdraw::*;
 pub use withdraw_and_close::*;
+pub use withdraw_exact_tokens::*;
+pub use withdraw_to_native::*;
 
 pub mod protocol_deposit;
 //pub mod protocol_initialize;
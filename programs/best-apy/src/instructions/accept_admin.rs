@@ -0,0 +1,38 @@
+use crate::config::ConfigAccount;
+use crate::error::ErrorCode;
+use crate::CONFIG_ACCOUNT_SEED;
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct AcceptAdminEvent {
+    previous_admin: Pubkey,
+    admin: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump,
+        constraint = config_account.pending_admin == user_signer.key() @ ErrorCode::UnauthorizedUser
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+}
+
+/// Accept the admin authority transfer
+pub fn handler(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let config = &mut ctx.accounts.config_account;
+    let previous_admin = config.admin;
+
+    config.admin = config.pending_admin;
+    config.pending_admin = Pubkey::default();
+
+    emit!(AcceptAdminEvent {
+        previous_admin,
+        admin: config.admin,
+    });
+
+    Ok(())
+}
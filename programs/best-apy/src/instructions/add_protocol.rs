@@ -1,7 +1,9 @@
+use crate::config::ConfigAccount;
 use crate::error::ErrorCode;
 use crate::protocols::Protocols;
+use crate::registry::RegistryAccount;
 use crate::vault::{ProtocolData, VaultAccount};
-use crate::VAULT_ACCOUNT_SEED;
+use crate::{CONFIG_ACCOUNT_SEED, REGISTRY_SEED, VAULT_ACCOUNT_SEED};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use std::convert::TryInto;
@@ -9,12 +11,23 @@ use std::convert::TryInto;
 #[derive(Accounts)]
 pub struct AddProtocol<'info> {
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     #[account(
         mut,
         seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
         bump = vault_account.bumps.vault
     )]
     pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_account.bump
+    )]
+    pub registry_account: Box<Account<'info, RegistryAccount>>,
 }
 
 /// Add a new protocol to the vault account
@@ -34,5 +47,10 @@ pub fn handler(ctx: Context<AddProtocol>, protocol_id: u8) -> Result<()> {
         });
     }
 
+    // Vaults created before the registry record their protocols once registered
+    if let Some(entry) = ctx.accounts.registry_account.get_mut(&vault.key()) {
+        entry.add_protocol(protocol_id)?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,133 @@
+use crate::macros::generate_seeds;
+use crate::ticket::{WithdrawEpochAccount, WithdrawTicketAccount};
+use crate::vault::VaultAccount;
+use crate::{
+    VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, VAULT_TICKET_MINT_SEED, WITHDRAW_EPOCH_SEED,
+    WITHDRAW_TICKET_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
+
+#[event]
+pub struct CancelWithdrawTicketEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    epoch: u64,
+    lp_amount: u64,
+    amount: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(bump_user: u8)]
+pub struct CancelWithdrawTicket<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = user_lp_token_account.owner == *user_signer.key,
+        constraint = user_lp_token_account.mint == vault_lp_token_mint_pubkey.key()
+    )]
+    pub user_lp_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = vault_user_ticket_account.owner == vault_account.key(),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_ticket_mint_pubkey.key().as_ref(), user_signer.key().as_ref()],
+        bump = bump_user
+    )]
+    pub vault_user_ticket_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bumps.lp_token_mint
+    )]
+    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = vault_ticket_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bump_ticket_mint
+    )]
+    pub vault_ticket_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = vault_lp_token_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_lp_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_TICKET_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump = withdraw_ticket_account.bump
+    )]
+    pub withdraw_ticket_account: Box<Account<'info, WithdrawTicketAccount>>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_EPOCH_SEED, vault_account.key().as_ref(), &withdraw_ticket_account.epoch.to_le_bytes()],
+        bump = withdraw_epoch_account.bump
+    )]
+    pub withdraw_epoch_account: Box<Account<'info, WithdrawEpochAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> CancelWithdrawTicket<'info> {
+    fn transfer_from_vault_to_user_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.vault_lp_token_account.to_account_info(),
+                to: self.user_lp_token_account.to_account_info(),
+                authority: self.vault_account.to_account_info(),
+            },
+        )
+    }
+
+    fn burn_ticket_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Burn {
+                mint: self.vault_ticket_mint_pubkey.to_account_info(),
+                from: self.vault_user_ticket_account.to_account_info(),
+                authority: self.vault_account.to_account_info(),
+            },
+        )
+    }
+}
+
+/// Cancel `lp_amount` locked LPs of a withdrawal ticket, burning the ticket tokens and sending
+/// the LPs back to the user. Only possible while the ticket epoch has not started being filled.
+/// The withdrawal fee charged when opening the ticket is not refunded
+pub fn handler(ctx: Context<CancelWithdrawTicket>, _bump_user: u8, lp_amount: u64) -> Result<()> {
+    let amount = ctx.accounts.withdraw_ticket_account.cancel(lp_amount)?;
+    ctx.accounts
+        .withdraw_epoch_account
+        .remove_ticket(lp_amount, amount)?;
+
+    let seeds = generate_seeds!(ctx.accounts.vault_account);
+    let signer = &[&seeds[..]];
+
+    token::burn(
+        ctx.accounts.burn_ticket_ctx().with_signer(signer),
+        lp_amount,
+    )?;
+    token::transfer(
+        ctx.accounts
+            .transfer_from_vault_to_user_ctx()
+            .with_signer(signer),
+        lp_amount,
+    )?;
+
+    emit!(CancelWithdrawTicketEvent {
+        vault: ctx.accounts.vault_account.key(),
+        user: ctx.accounts.user_signer.key(),
+        epoch: ctx.accounts.withdraw_ticket_account.epoch,
+        lp_amount,
+        amount,
+    });
+
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use crate::error::ErrorCode;
 use crate::macros::generate_seeds;
-use crate::vault::{LpPrice, VaultAccount};
-use crate::{VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, VAULT_TICKET_MINT_SEED};
+use crate::ticket::{WithdrawEpochAccount, WithdrawTicketAccount};
+use crate::vault::VaultAccount;
+use crate::{
+    VAULT_ACCOUNT_SEED, VAULT_TICKET_MINT_SEED, WITHDRAW_EPOCH_SEED, WITHDRAW_TICKET_SEED,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
 use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
@@ -28,13 +31,6 @@ pub struct CloseWithdrawTicket<'info> {
         bump = vault_account.bumps.vault
     )]
     pub vault_account: Box<Account<'info, VaultAccount>>,
-    #[account(
-        mut,
-        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
-        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
-        bump = vault_account.bumps.lp_token_mint
-    )]
-    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
     #[account(
         mut,
         constraint = vault_ticket_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
@@ -50,21 +46,20 @@ pub struct CloseWithdrawTicket<'info> {
     pub vault_input_token_account: Account<'info, TokenAccount>,
     #[account(
         mut,
-        associated_token::mint = vault_lp_token_mint_pubkey,
-        associated_token::authority = vault_account,
+        seeds = [WITHDRAW_TICKET_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump = withdraw_ticket_account.bump
+    )]
+    pub withdraw_ticket_account: Box<Account<'info, WithdrawTicketAccount>>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_EPOCH_SEED, vault_account.key().as_ref(), &withdraw_ticket_account.epoch.to_le_bytes()],
+        bump = withdraw_epoch_account.bump
     )]
-    pub vault_lp_token_account: Account<'info, TokenAccount>,
+    pub withdraw_epoch_account: Box<Account<'info, WithdrawEpochAccount>>,
     pub token_program: Program<'info, Token>,
 }
 
 impl<'info> CloseWithdrawTicket<'info> {
-    fn current_lp_price(&self) -> LpPrice {
-        LpPrice {
-            total_tokens: self.vault_account.current_tvl,
-            minted_tokens: self.vault_lp_token_mint_pubkey.supply,
-        }
-    }
-
     fn transfer_from_vault_to_user_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         CpiContext::new(
             self.token_program.to_account_info(),
@@ -76,17 +71,6 @@ impl<'info> CloseWithdrawTicket<'info> {
         )
     }
 
-    fn burn_lps_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
-        CpiContext::new(
-            self.token_program.to_account_info(),
-            Burn {
-                mint: self.vault_lp_token_mint_pubkey.to_account_info(),
-                from: self.vault_lp_token_account.to_account_info(),
-                authority: self.vault_account.to_account_info(),
-            },
-        )
-    }
-
     fn burn_ticket_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
         CpiContext::new(
             self.token_program.to_account_info(),
@@ -99,44 +83,49 @@ impl<'info> CloseWithdrawTicket<'info> {
     }
 }
 
-/// Close a withdrawal ticket
-pub fn handler(ctx: Context<CloseWithdrawTicket>, _bump_user: u8, lp_amount: u64) -> Result<()> {
-    let current_price = ctx.accounts.current_lp_price();
-    let previous_price = ctx.accounts.vault_account.previous_lp_price;
-
-    if previous_price != LpPrice::default() {
-        require!(current_price >= previous_price, ErrorCode::InvalidLpPrice);
-    }
-
-    // Use previous value of LP in order to avoid depositors.
-    // Also add a 1 lamport fee due precision errors when withdrawing from lending protocols
-    let amount = previous_price.lp_to_token(lp_amount)?;
-    let amount_conservative = amount.saturating_sub(1);
+/// Close a withdrawal ticket, paying the user its share of the amount filled so far in the
+/// ticket epoch and reverting if it is less than `min_tokens_out`. The ticket tokens are burned
+/// once the epoch is completely filled, even if the rounded down payout is zero
+pub fn handler(
+    ctx: Context<CloseWithdrawTicket>,
+    _bump_user: u8,
+    min_tokens_out: u64,
+) -> Result<()> {
+    let amount = ctx
+        .accounts
+        .withdraw_epoch_account
+        .claimable_amount(&ctx.accounts.withdraw_ticket_account)?;
+    let epoch_filled = ctx.accounts.withdraw_epoch_account.is_filled();
 
-    require!(amount_conservative > 1, ErrorCode::InvalidZeroWithdraw);
+    require!(amount > 0 || epoch_filled, ErrorCode::InvalidZeroWithdraw);
+    require!(amount >= min_tokens_out, ErrorCode::SlippageExceeded);
 
     let seeds = generate_seeds!(ctx.accounts.vault_account);
     let signer = &[&seeds[..]];
 
-    token::burn(ctx.accounts.burn_lps_ctx().with_signer(signer), lp_amount)?;
-    token::burn(
-        ctx.accounts.burn_ticket_ctx().with_signer(signer),
-        lp_amount,
-    )?;
-    token::transfer(
-        ctx.accounts
-            .transfer_from_vault_to_user_ctx()
-            .with_signer(signer),
-        amount_conservative,
-    )?;
+    if epoch_filled {
+        token::burn(
+            ctx.accounts.burn_ticket_ctx().with_signer(signer),
+            ctx.accounts.withdraw_ticket_account.lp_amount,
+        )?;
+    }
+    if amount > 0 {
+        token::transfer(
+            ctx.accounts
+                .transfer_from_vault_to_user_ctx()
+                .with_signer(signer),
+            amount,
+        )?;
+    }
 
-    // Update total withdraw (assuming we have lost 1 lamport due precision errors)
-    ctx.accounts.vault_account.current_tvl = ctx
+    let dust = ctx
         .accounts
-        .vault_account
-        .current_tvl
-        .checked_sub(amount)
-        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        .withdraw_epoch_account
+        .claim(&ctx.accounts.withdraw_ticket_account, amount)?;
+    ctx.accounts
+        .withdraw_ticket_account
+        .claim(amount, epoch_filled)?;
+    ctx.accounts.vault_account.pay_ticket(amount, dust)?;
 
     Ok(())
 }
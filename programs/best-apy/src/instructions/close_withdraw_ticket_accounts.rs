@@ -0,0 +1,64 @@
+use crate::error::ErrorCode;
+use crate::macros::generate_seeds;
+use crate::ticket::WithdrawTicketAccount;
+use crate::vault::VaultAccount;
+use crate::{VAULT_ACCOUNT_SEED, VAULT_TICKET_MINT_SEED, WITHDRAW_TICKET_SEED};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount};
+
+#[derive(Accounts)]
+#[instruction(bump_user: u8)]
+pub struct CloseWithdrawTicketAccounts<'info> {
+    #[account(mut)]
+    pub user_signer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = vault_user_ticket_account.owner == vault_account.key(),
+        constraint = vault_user_ticket_account.amount == 0 @ ErrorCode::InvalidTicketAmount,
+        seeds = [VAULT_TICKET_MINT_SEED, vault_ticket_mint_pubkey.key().as_ref(), user_signer.key().as_ref()],
+        bump = bump_user
+    )]
+    pub vault_user_ticket_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        constraint = vault_ticket_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bump_ticket_mint
+    )]
+    pub vault_ticket_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        mut,
+        close = user_signer,
+        constraint = withdraw_ticket_account.is_empty() @ ErrorCode::InvalidTicketAmount,
+        seeds = [WITHDRAW_TICKET_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump = withdraw_ticket_account.bump
+    )]
+    pub withdraw_ticket_account: Box<Account<'info, WithdrawTicketAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> CloseWithdrawTicketAccounts<'info> {
+    fn close_ticket_account_ctx(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.vault_user_ticket_account.to_account_info(),
+                destination: self.user_signer.to_account_info(),
+                authority: self.vault_account.to_account_info(),
+            },
+        )
+    }
+}
+
+/// Close the empty withdrawal ticket accounts of the user, reclaiming their rent
+pub fn handler(ctx: Context<CloseWithdrawTicketAccounts>, _bump_user: u8) -> Result<()> {
+    let seeds = generate_seeds!(ctx.accounts.vault_account);
+    let signer = &[&seeds[..]];
+
+    token::close_account(ctx.accounts.close_ticket_account_ctx().with_signer(signer))
+}
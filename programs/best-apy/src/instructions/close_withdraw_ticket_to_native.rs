@@ -0,0 +1,126 @@
+use crate::instructions::{self, CloseWithdrawTicket};
+use crate::ticket::{WithdrawEpochAccount, WithdrawTicketAccount};
+use crate::vault::VaultAccount;
+use crate::{
+    USER_WRAPPED_ACCOUNT_SEED, VAULT_ACCOUNT_SEED, VAULT_TICKET_MINT_SEED, WITHDRAW_EPOCH_SEED,
+    WITHDRAW_TICKET_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount};
+
+#[derive(Accounts)]
+#[instruction(bump_user: u8)]
+pub struct CloseWithdrawTicketToNative<'info> {
+    #[account(mut)]
+    pub user_signer: Signer<'info>,
+    #[account(
+        init,
+        payer = user_signer,
+        seeds = [USER_WRAPPED_ACCOUNT_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = user_signer,
+    )]
+    pub user_wrapped_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = vault_user_ticket_account.owner == vault_account.key(),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_ticket_mint_pubkey.key().as_ref(), user_signer.key().as_ref()],
+        bump = bump_user
+    )]
+    pub vault_user_ticket_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = vault_account.input_mint_pubkey == spl_token::native_mint::ID,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        mut,
+        constraint = vault_ticket_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bump_ticket_mint
+    )]
+    pub vault_ticket_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = vault_account.input_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_input_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_TICKET_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump = withdraw_ticket_account.bump
+    )]
+    pub withdraw_ticket_account: Box<Account<'info, WithdrawTicketAccount>>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_EPOCH_SEED, vault_account.key().as_ref(), &withdraw_ticket_account.epoch.to_le_bytes()],
+        bump = withdraw_epoch_account.bump
+    )]
+    pub withdraw_epoch_account: Box<Account<'info, WithdrawEpochAccount>>,
+    #[account(address = spl_token::native_mint::ID)]
+    pub native_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> CloseWithdrawTicketToNative<'info> {
+    fn close_wrapped_account_ctx(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.user_wrapped_account.to_account_info(),
+                destination: self.user_signer.to_account_info(),
+                authority: self.user_signer.to_account_info(),
+            },
+        )
+    }
+
+    fn to_close_withdraw_ticket_accounts(&self) -> CloseWithdrawTicket<'info> {
+        CloseWithdrawTicket {
+            user_signer: self.user_signer.clone(),
+            user_input_token_account: self.user_wrapped_account.clone(),
+            vault_user_ticket_account: self.vault_user_ticket_account.clone(),
+            vault_account: self.vault_account.clone(),
+            vault_ticket_mint_pubkey: self.vault_ticket_mint_pubkey.clone(),
+            vault_input_token_account: self.vault_input_token_account.clone(),
+            withdraw_ticket_account: self.withdraw_ticket_account.clone(),
+            withdraw_epoch_account: self.withdraw_epoch_account.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+}
+
+/// Close a withdrawal ticket into a temporary wrapped SOL account, which is closed afterwards
+/// sending the native SOL back to the user
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, CloseWithdrawTicketToNative<'info>>,
+    bump_user: u8,
+    min_tokens_out: u64,
+) -> Result<()> {
+    let mut close_withdraw_ticket_accounts = ctx.accounts.to_close_withdraw_ticket_accounts();
+    instructions::close_withdraw_ticket::handler(
+        Context::new(
+            ctx.program_id,
+            &mut close_withdraw_ticket_accounts,
+            ctx.remaining_accounts,
+            ctx.bumps,
+        ),
+        bump_user,
+        min_tokens_out,
+    )?;
+
+    // Keep the vault, ticket and epoch state updated by the withdrawal
+    ctx.accounts.vault_account = close_withdraw_ticket_accounts.vault_account;
+    ctx.accounts.withdraw_ticket_account = close_withdraw_ticket_accounts.withdraw_ticket_account;
+    ctx.accounts.withdraw_epoch_account = close_withdraw_ticket_accounts.withdraw_epoch_account;
+
+    token::close_account(ctx.accounts.close_wrapped_account_ctx())?;
+
+    Ok(())
+}
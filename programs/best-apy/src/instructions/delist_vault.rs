@@ -0,0 +1,45 @@
+use crate::config::ConfigAccount;
+use crate::registry::{RegistryAccount, VaultStatus};
+use crate::vault::VaultAccount;
+use crate::{CONFIG_ACCOUNT_SEED, REGISTRY_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct DelistVaultEvent {
+    vault: Pubkey,
+    token: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct DelistVault<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        mut,
+        seeds = [REGISTRY_SEED],
+        bump = registry_account.bump
+    )]
+    pub registry_account: Box<Account<'info, RegistryAccount>>,
+}
+
+/// Mark the vault as delisted in the registry
+pub fn handler(ctx: Context<DelistVault>) -> Result<()> {
+    let vault = ctx.accounts.vault_account.key();
+    ctx.accounts.registry_account.entry_mut(&vault)?.status = VaultStatus::Delisted;
+
+    emit!(DelistVaultEvent {
+        vault,
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+    });
+
+    Ok(())
+}
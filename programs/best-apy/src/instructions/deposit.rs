@@ -33,15 +33,15 @@ pub struct Deposit<'info> {
         associated_token::authority = vault_account,
     )]
     pub vault_input_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = vault_account.dao_treasury_lp_token_account)]
+    pub dao_treasury_lp_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
 }
 
 impl<'info> Deposit<'info> {
     fn current_lp_price(&self) -> LpPrice {
-        LpPrice {
-            total_tokens: self.vault_account.current_tvl,
-            minted_tokens: self.vault_lp_token_mint_pubkey.supply,
-        }
+        self.vault_account
+            .deposit_lp_price(self.vault_lp_token_mint_pubkey.supply)
     }
 
     fn transfer_from_user_to_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
@@ -65,20 +65,41 @@ impl<'info> Deposit<'info> {
             },
         )
     }
+
+    fn mint_lp_to_treasury_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            MintTo {
+                mint: self.vault_lp_token_mint_pubkey.to_account_info(),
+                to: self.dao_treasury_lp_token_account.to_account_info(),
+                authority: self.vault_account.to_account_info(),
+            },
+        )
+    }
 }
 
-/// Deposit user input tokens into the vault account
-pub fn handler(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+/// Deposit user input tokens into the vault account, reverting if the user would receive less
+/// than `min_lp_out` LP tokens. The LP price excludes the pending loss, so deposits are rejected
+/// while the loss outweighs the gains since the last refresh
+pub fn handler(ctx: Context<Deposit>, amount: u64, min_lp_out: u64) -> Result<()> {
     let current_price = ctx.accounts.current_lp_price();
     let previous_price = ctx.accounts.vault_account.previous_lp_price;
 
-    if previous_price != LpPrice::default() {
+    if previous_price.minted_tokens != 0 {
         require!(current_price >= previous_price, ErrorCode::InvalidLpPrice);
     }
 
     require!(amount >= 100, ErrorCode::InvalidDepositAmount);
 
     let lp_amount = current_price.token_to_lp(amount)?;
+    require!(lp_amount > 0, ErrorCode::InvalidDepositAmount);
+
+    let lp_fee = ctx.accounts.vault_account.fees.deposit_fee_lp(lp_amount)?;
+    let lp_amount_to_user = lp_amount
+        .checked_sub(lp_fee)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+    require!(lp_amount_to_user >= min_lp_out, ErrorCode::SlippageExceeded);
 
     let seeds = generate_seeds!(ctx.accounts.vault_account);
     let signer = &[&seeds[..]];
@@ -86,8 +107,14 @@ pub fn handler(ctx: Context<Deposit>, amount: u64) -> Result<()> {
     token::transfer(ctx.accounts.transfer_from_user_to_vault_ctx(), amount)?;
     token::mint_to(
         ctx.accounts.mint_lp_to_user_ctx().with_signer(signer),
-        lp_amount,
+        lp_amount_to_user,
     )?;
+    if lp_fee > 0 {
+        token::mint_to(
+            ctx.accounts.mint_lp_to_treasury_ctx().with_signer(signer),
+            lp_fee,
+        )?;
+    }
 
     // Update total deposited amounts
     ctx.accounts.vault_account.current_tvl = ctx
@@ -39,6 +39,8 @@ pub struct DepositFromNative<'info> {
         associated_token::authority = vault_account,
     )]
     pub vault_input_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = vault_account.dao_treasury_lp_token_account)]
+    pub dao_treasury_lp_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
@@ -71,6 +73,7 @@ impl<'info> DepositFromNative<'info> {
             vault_account: self.vault_account.clone(),
             vault_lp_token_mint_pubkey: self.vault_lp_token_mint_pubkey.clone(),
             vault_input_token_account: self.vault_input_token_account.clone(),
+            dao_treasury_lp_token_account: self.dao_treasury_lp_token_account.clone(),
             token_program: self.token_program.clone(),
         }
     }
@@ -94,23 +97,30 @@ pub fn sync_native<'a, 'b, 'c, 'info>(
     )?)
 }
 
-/// Deposit user input tokens into the vault account
+/// Deposit user input tokens into the vault account, wrapping them first. The deposit goes
+/// through the `deposit` handler, so it is also priced without the pending loss
 pub fn handler<'info>(
     ctx: Context<'_, '_, '_, 'info, DepositFromNative<'info>>,
     amount: u64,
+    min_lp_out: u64,
 ) -> Result<()> {
     system_program::transfer(ctx.accounts.system_program_transfer_ctx(), amount)?;
     sync_native(ctx.accounts.sync_native_ctx())?;
 
+    let mut deposit_accounts = ctx.accounts.to_deposit_accounts();
     instructions::deposit::handler(
         Context::new(
             ctx.program_id,
-            &mut ctx.accounts.to_deposit_accounts(),
+            &mut deposit_accounts,
             ctx.remaining_accounts,
             ctx.bumps,
         ),
         amount,
+        min_lp_out,
     )?;
 
+    // Keep the vault state updated by the deposit
+    ctx.accounts.vault_account = deposit_accounts.vault_account;
+
     Ok(())
 }
@@ -0,0 +1,137 @@
+use crate::config::ConfigAccount;
+use crate::error::ErrorCode;
+use crate::macros::generate_seeds;
+use crate::ticket::WithdrawEpochAccount;
+use crate::vault::VaultAccount;
+use crate::{
+    CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, WITHDRAW_EPOCH_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount};
+
+#[event]
+pub struct FillWithdrawEpochEvent {
+    vault: Pubkey,
+    epoch: u64,
+    amount: u64,
+    lp_amount: u64,
+    filled_amount: u64,
+    owed_amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct FillWithdrawEpoch<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_EPOCH_SEED, vault_account.key().as_ref(), &vault_account.pending_withdraw_epoch.to_le_bytes()],
+        bump = withdraw_epoch_account.bump
+    )]
+    pub withdraw_epoch_account: Box<Account<'info, WithdrawEpochAccount>>,
+    #[account(
+        mut,
+        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bumps.lp_token_mint
+    )]
+    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        associated_token::mint = vault_account.input_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_input_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = vault_lp_token_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_lp_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> FillWithdrawEpoch<'info> {
+    fn burn_lps_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Burn {
+                mint: self.vault_lp_token_mint_pubkey.to_account_info(),
+                from: self.vault_lp_token_account.to_account_info(),
+                authority: self.vault_account.to_account_info(),
+            },
+        )
+    }
+}
+
+/// Set aside up to `amount` idle tokens for the tickets of the oldest pending epoch, burning the
+/// proportional locked LPs. The first fill closes the epoch to new tickets. Any protocol
+/// withdrawals needed to make the liquidity idle can be chained in front of this instruction
+pub fn handler(ctx: Context<FillWithdrawEpoch>, amount: u64) -> Result<()> {
+    let vault_account = &mut ctx.accounts.vault_account;
+    let withdraw_epoch = &mut ctx.accounts.withdraw_epoch_account;
+
+    if withdraw_epoch.is_open() {
+        withdraw_epoch.finalize(
+            &vault_account.withdraw_lp_price()?,
+            Clock::get()?.unix_timestamp,
+        )?;
+        vault_account.withdraw_epoch = vault_account
+            .withdraw_epoch
+            .checked_add(1)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+    }
+
+    let outstanding = withdraw_epoch
+        .owed_amount
+        .checked_sub(withdraw_epoch.filled_amount)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+    require!(amount <= outstanding, ErrorCode::InvalidTicketAmount);
+
+    let available_amount =
+        vault_account.available_idle(ctx.accounts.vault_input_token_account.amount);
+    let (amount, lp_amount) = withdraw_epoch.fill(std::cmp::min(amount, available_amount))?;
+
+    vault_account.current_tvl = vault_account
+        .current_tvl
+        .checked_sub(amount)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+    vault_account.reserved_for_tickets = vault_account
+        .reserved_for_tickets
+        .checked_add(amount)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+    if withdraw_epoch.is_filled() {
+        vault_account.pending_withdraw_epoch = vault_account
+            .pending_withdraw_epoch
+            .checked_add(1)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+    }
+
+    emit!(FillWithdrawEpochEvent {
+        vault: vault_account.key(),
+        epoch: withdraw_epoch.epoch,
+        amount,
+        lp_amount,
+        filled_amount: withdraw_epoch.filled_amount,
+        owed_amount: withdraw_epoch.owed_amount,
+    });
+
+    if lp_amount > 0 {
+        let seeds = generate_seeds!(ctx.accounts.vault_account);
+        let signer = &[&seeds[..]];
+        token::burn(ctx.accounts.burn_lps_ctx().with_signer(signer), lp_amount)?;
+    }
+
+    Ok(())
+}
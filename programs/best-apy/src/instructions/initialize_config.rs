@@ -0,0 +1,28 @@
+use crate::config::ConfigAccount;
+use crate::CONFIG_ACCOUNT_SEED;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub user_signer: Signer<'info>,
+    #[account(
+        init,
+        payer = user_signer,
+        space = 8 + ConfigAccount::SIZE,
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Initialize the config account, assigning every role to the signer
+pub fn handler(ctx: Context<InitializeConfig>) -> Result<()> {
+    ctx.accounts.config_account.set_inner(ConfigAccount::init(
+        *ctx.bumps.get("config_account").unwrap(),
+        ctx.accounts.user_signer.key(),
+    ));
+
+    Ok(())
+}
@@ -1,5 +1,8 @@
+use crate::config::ConfigAccount;
 use crate::vault::VaultAccount;
-use crate::{VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, VAULT_TICKET_MINT_SEED};
+use crate::{
+    CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, VAULT_TICKET_MINT_SEED,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
 use anchor_spl::token::{Mint, Token};
@@ -8,6 +11,11 @@ use anchor_spl::token::{Mint, Token};
 pub struct InitializeTicketMint<'info> {
     #[account(mut)]
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     #[account(
         mut,
         seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
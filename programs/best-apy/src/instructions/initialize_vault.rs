@@ -1,6 +1,11 @@
+use crate::config::ConfigAccount;
+use crate::registry::{RegistryAccount, RegistryEntry};
 use crate::vault::{Bumps, InitVaultAccountParams, VaultAccount};
-use crate::TREASURY_PUBKEY;
-use crate::{VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED};
+use crate::vault_index::VaultIndexAccount;
+use crate::{
+    CONFIG_ACCOUNT_SEED, REGISTRY_SEED, VAULT_ACCOUNT_SEED, VAULT_INDEX_SEED,
+    VAULT_LP_TOKEN_MINT_SEED,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use anchor_spl::associated_token::AssociatedToken;
@@ -11,6 +16,11 @@ use anchor_spl::token::{Mint, Token, TokenAccount};
 pub struct InitializeVault<'info> {
     #[account(mut)]
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     pub input_token_mint_address: Account<'info, Mint>,
     #[account(
         init,
@@ -20,6 +30,22 @@ pub struct InitializeVault<'info> {
         bump,
     )]
     pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + VaultIndexAccount::SIZE,
+        seeds = [VAULT_INDEX_SEED, input_token_mint_address.key().as_ref()],
+        bump,
+    )]
+    pub vault_index_account: Box<Account<'info, VaultIndexAccount>>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + RegistryAccount::SIZE,
+        seeds = [REGISTRY_SEED],
+        bump,
+    )]
+    pub registry_account: Box<Account<'info, RegistryAccount>>,
     #[account(
         init,
         payer = user_signer,
@@ -43,8 +69,7 @@ pub struct InitializeVault<'info> {
         associated_token::authority = dao_treasury_owner,
     )]
     pub dao_treasury_lp_token_account: Account<'info, TokenAccount>,
-    #[account(constraint = dao_treasury_owner.key == &TREASURY_PUBKEY)]
-    /// CHECKED: address is checked
+    /// CHECK: any owner chosen by the admin
     pub dao_treasury_owner: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -65,5 +90,22 @@ pub fn handler(ctx: Context<InitializeVault>, account_number: u8) -> Result<()>
             dao_treasury_lp_token_account: ctx.accounts.dao_treasury_lp_token_account.key(),
         }));
 
+    let vault_index = &mut ctx.accounts.vault_index_account;
+    if vault_index.input_mint_pubkey == Pubkey::default() {
+        vault_index.bump = *ctx.bumps.get("vault_index_account").unwrap();
+        vault_index.input_mint_pubkey = ctx.accounts.input_token_mint_address.key();
+    }
+    vault_index.insert(account_number)?;
+
+    let registry = &mut ctx.accounts.registry_account;
+    registry.bump = *ctx.bumps.get("registry_account").unwrap();
+    registry.register(RegistryEntry {
+        vault: ctx.accounts.vault_account.key(),
+        input_mint_pubkey: ctx.accounts.input_token_mint_address.key(),
+        seed_number: account_number,
+        lp_token_mint_pubkey: ctx.accounts.vault_lp_token_mint_pubkey.key(),
+        ..RegistryEntry::default()
+    })?;
+
     Ok(())
 }
@@ -1,7 +1,8 @@
+use crate::config::ConfigAccount;
 use crate::macros::generate_seeds;
 use crate::protocols::Protocols;
 use crate::vault::{AccumulatedRewards, VaultAccount};
-use crate::VAULT_ACCOUNT_SEED;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use anchor_spl::token::{Mint, Token, TokenAccount};
@@ -11,6 +12,11 @@ use mango_v3_reimbursement::cpi::accounts::{CreateReimbursementAccount, Reimburs
 pub struct MangoReimbursement<'info> {
     #[account(mut)]
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     ///CHECK: Mango checks this
     #[account(mut)]
     pub group: AccountInfo<'info>,
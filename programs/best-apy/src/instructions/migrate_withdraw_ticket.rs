@@ -0,0 +1,128 @@
+use crate::error::ErrorCode;
+use crate::ticket::{WithdrawEpochAccount, WithdrawTicketAccount};
+use crate::vault::{LpPrice, VaultAccount};
+use crate::{
+    VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, VAULT_TICKET_MINT_SEED, WITHDRAW_EPOCH_SEED,
+    WITHDRAW_TICKET_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{Mint, TokenAccount};
+
+#[event]
+pub struct MigrateWithdrawTicketEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    epoch: u64,
+    lp_amount: u64,
+    amount: u64,
+    lp_price: LpPrice,
+}
+
+#[derive(Accounts)]
+#[instruction(bump_user: u8)]
+pub struct MigrateWithdrawTicket<'info> {
+    #[account(mut)]
+    pub user_signer: Signer<'info>,
+    #[account(
+        constraint = vault_user_ticket_account.owner == vault_account.key(),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_ticket_mint_pubkey.key().as_ref(), user_signer.key().as_ref()],
+        bump = bump_user
+    )]
+    pub vault_user_ticket_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bumps.lp_token_mint
+    )]
+    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        constraint = vault_ticket_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bump_ticket_mint
+    )]
+    pub vault_ticket_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + WithdrawTicketAccount::SIZE,
+        seeds = [WITHDRAW_TICKET_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump,
+    )]
+    pub withdraw_ticket_account: Box<Account<'info, WithdrawTicketAccount>>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + WithdrawEpochAccount::SIZE,
+        seeds = [WITHDRAW_EPOCH_SEED, vault_account.key().as_ref(), &vault_account.withdraw_epoch.to_le_bytes()],
+        bump,
+    )]
+    pub withdraw_epoch_account: Box<Account<'info, WithdrawEpochAccount>>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateWithdrawTicket<'info> {
+    fn current_lp_price(&self) -> LpPrice {
+        LpPrice {
+            total_tokens: self.vault_account.current_tvl,
+            minted_tokens: self.vault_lp_token_mint_pubkey.supply,
+        }
+    }
+}
+
+/// Queue the LPs locked by a legacy withdrawal ticket (ticket tokens not backed by a withdrawal
+/// ticket account) in the current epoch, so that they can be closed as a regular ticket. The LPs
+/// and the ticket tokens are already held by the vault, so no tokens are moved
+pub fn handler(ctx: Context<MigrateWithdrawTicket>, _bump_user: u8) -> Result<()> {
+    let current_price = ctx.accounts.current_lp_price();
+    let previous_price = ctx.accounts.vault_account.previous_lp_price;
+
+    if previous_price.minted_tokens != 0 {
+        require!(current_price >= previous_price, ErrorCode::InvalidLpPrice);
+    }
+
+    let legacy_lp_amount = ctx
+        .accounts
+        .vault_user_ticket_account
+        .amount
+        .checked_sub(ctx.accounts.withdraw_ticket_account.lp_amount)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+    require!(legacy_lp_amount > 0, ErrorCode::InvalidTicketAmount);
+
+    let now = Clock::get()?.unix_timestamp;
+    let vault_key = ctx.accounts.vault_account.key();
+    let epoch = ctx.accounts.vault_account.withdraw_epoch;
+    let lp_price = ctx.accounts.vault_account.withdraw_lp_price()?;
+
+    let ticket = &mut ctx.accounts.withdraw_ticket_account;
+    if ticket.vault == Pubkey::default() {
+        ticket.bump = *ctx.bumps.get("withdraw_ticket_account").unwrap();
+        ticket.vault = vault_key;
+        ticket.owner = ctx.accounts.user_signer.key();
+    }
+    let amount = ticket.open(epoch, legacy_lp_amount, lp_price, now)?;
+
+    let withdraw_epoch = &mut ctx.accounts.withdraw_epoch_account;
+    if withdraw_epoch.vault == Pubkey::default() {
+        withdraw_epoch.bump = *ctx.bumps.get("withdraw_epoch_account").unwrap();
+        withdraw_epoch.vault = vault_key;
+        withdraw_epoch.epoch = epoch;
+    }
+    withdraw_epoch.add_ticket(legacy_lp_amount, amount, now)?;
+
+    emit!(MigrateWithdrawTicketEvent {
+        vault: vault_key,
+        user: ctx.accounts.user_signer.key(),
+        epoch,
+        lp_amount: legacy_lp_amount,
+        amount,
+        lp_price,
+    });
+
+    Ok(())
+}
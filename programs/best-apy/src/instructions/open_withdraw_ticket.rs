@@ -1,14 +1,29 @@
 use crate::error::ErrorCode;
 use crate::macros::generate_seeds;
+use crate::ticket::{WithdrawEpochAccount, WithdrawTicketAccount};
 use crate::vault::{LpPrice, VaultAccount};
-use crate::{VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, VAULT_TICKET_MINT_SEED};
+use crate::{
+    VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED, VAULT_TICKET_MINT_SEED, WITHDRAW_EPOCH_SEED,
+    WITHDRAW_TICKET_SEED,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
 use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
 
+#[event]
+pub struct OpenWithdrawTicketEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    epoch: u64,
+    lp_amount: u64,
+    amount: u64,
+    lp_price: LpPrice,
+}
+
 #[derive(Accounts)]
 #[instruction(bump_user: u8)]
 pub struct OpenWithdrawTicket<'info> {
+    #[account(mut)]
     pub user_signer: Signer<'info>,
     #[account(
         mut,
@@ -48,7 +63,26 @@ pub struct OpenWithdrawTicket<'info> {
         associated_token::authority = vault_account,
     )]
     pub vault_lp_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = vault_account.dao_treasury_lp_token_account)]
+    pub dao_treasury_lp_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + WithdrawTicketAccount::SIZE,
+        seeds = [WITHDRAW_TICKET_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump,
+    )]
+    pub withdraw_ticket_account: Box<Account<'info, WithdrawTicketAccount>>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + WithdrawEpochAccount::SIZE,
+        seeds = [WITHDRAW_EPOCH_SEED, vault_account.key().as_ref(), &vault_account.withdraw_epoch.to_le_bytes()],
+        bump,
+    )]
+    pub withdraw_epoch_account: Box<Account<'info, WithdrawEpochAccount>>,
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 impl<'info> OpenWithdrawTicket<'info> {
@@ -70,6 +104,19 @@ impl<'info> OpenWithdrawTicket<'info> {
         )
     }
 
+    fn transfer_lps_from_user_to_treasury_ctx(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.user_lp_token_account.to_account_info(),
+                to: self.dao_treasury_lp_token_account.to_account_info(),
+                authority: self.user_signer.to_account_info(),
+            },
+        )
+    }
+
     fn mint_ticket_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
         CpiContext::new(
             self.token_program.to_account_info(),
@@ -82,23 +129,69 @@ impl<'info> OpenWithdrawTicket<'info> {
     }
 }
 
-/// Open a withdrawal ticket (for delayed withdrawals)
+/// Open a withdrawal ticket (for delayed withdrawals), queuing it in the current epoch. The LPs
+/// are locked at the previous LP price net of unrecognized losses, after charging the withdrawal
+/// fee
 pub fn handler(ctx: Context<OpenWithdrawTicket>, _bump_user: u8, lp_amount: u64) -> Result<()> {
     let current_price = ctx.accounts.current_lp_price();
     let previous_price = ctx.accounts.vault_account.previous_lp_price;
 
-    if previous_price != LpPrice::default() {
+    if previous_price.minted_tokens != 0 {
         require!(current_price >= previous_price, ErrorCode::InvalidLpPrice);
     }
 
+    let lp_fee = ctx.accounts.vault_account.fees.withdraw_fee_lp(lp_amount)?;
+    let lp_amount_to_lock = lp_amount
+        .checked_sub(lp_fee)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let vault_key = ctx.accounts.vault_account.key();
+    let epoch = ctx.accounts.vault_account.withdraw_epoch;
+
+    let ticket = &mut ctx.accounts.withdraw_ticket_account;
+    if ticket.vault == Pubkey::default() {
+        ticket.bump = *ctx.bumps.get("withdraw_ticket_account").unwrap();
+        ticket.vault = vault_key;
+        ticket.owner = ctx.accounts.user_signer.key();
+    }
+    let lp_price = ctx.accounts.vault_account.withdraw_lp_price()?;
+    let amount = ticket.open(epoch, lp_amount_to_lock, lp_price, now)?;
+
+    let withdraw_epoch = &mut ctx.accounts.withdraw_epoch_account;
+    if withdraw_epoch.vault == Pubkey::default() {
+        withdraw_epoch.bump = *ctx.bumps.get("withdraw_epoch_account").unwrap();
+        withdraw_epoch.vault = vault_key;
+        withdraw_epoch.epoch = epoch;
+    }
+    withdraw_epoch.add_ticket(lp_amount_to_lock, amount, now)?;
+
     let seeds = generate_seeds!(ctx.accounts.vault_account);
     let signer = &[&seeds[..]];
 
-    token::transfer(ctx.accounts.transfer_from_user_to_vault_ctx(), lp_amount)?;
+    if lp_fee > 0 {
+        token::transfer(
+            ctx.accounts.transfer_lps_from_user_to_treasury_ctx(),
+            lp_fee,
+        )?;
+    }
+    token::transfer(
+        ctx.accounts.transfer_from_user_to_vault_ctx(),
+        lp_amount_to_lock,
+    )?;
     token::mint_to(
         ctx.accounts.mint_ticket_ctx().with_signer(signer),
-        lp_amount,
+        lp_amount_to_lock,
     )?;
 
+    emit!(OpenWithdrawTicketEvent {
+        vault: vault_key,
+        user: ctx.accounts.user_signer.key(),
+        epoch,
+        lp_amount: lp_amount_to_lock,
+        amount,
+        lp_price,
+    });
+
     Ok(())
 }
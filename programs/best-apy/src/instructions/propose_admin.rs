@@ -0,0 +1,32 @@
+use crate::config::ConfigAccount;
+use crate::CONFIG_ACCOUNT_SEED;
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct ProposeAdminEvent {
+    admin: Pubkey,
+    pending_admin: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+}
+
+/// Propose a new admin, which has to accept the authority transfer
+pub fn handler(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+    ctx.accounts.config_account.pending_admin = new_admin;
+
+    emit!(ProposeAdminEvent {
+        admin: ctx.accounts.config_account.admin,
+        pending_admin: new_admin,
+    });
+
+    Ok(())
+}
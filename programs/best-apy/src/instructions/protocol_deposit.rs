@@ -1,9 +1,20 @@
+use crate::error::ErrorCode;
 use crate::protocols::Protocols;
 use crate::vault::{ProtocolData, VaultAccount};
 use crate::VAULT_ACCOUNT_SEED;
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount};
 
+#[event]
+pub struct ReserveShareClippedEvent {
+    vault: Pubkey,
+    protocol_id: u8,
+    /// Amount deposited after clipping
+    amount: u64,
+    /// Amount left out of the deposit
+    clipped_amount: u64,
+}
+
 /// Deposit into the protocol
 pub trait ProtocolDeposit<'info> {
     /// Return the protcol position in the vector
@@ -15,6 +26,9 @@ pub trait ProtocolDeposit<'info> {
     /// Compute the amount to deposit
     fn get_amount(&self, protocol_idx: usize) -> Result<u64>;
 
+    /// Total liquidity supplied to the protocol reserve, including the borrowed amount
+    fn reserve_supply(&self) -> Result<u64>;
+
     /// Deposit into the protocol
     fn cpi_deposit(&self, amount: u64) -> Result<()>;
 }
@@ -53,9 +67,46 @@ pub struct GenericDepositAccounts<'info> {
 }
 
 impl<'info> GenericDepositAccounts<'info> {
-    /// Compute the amount to deposit into the protocol
-    pub fn amount_to_deposit(&self, protocol_idx: usize) -> Result<u64> {
-        self.vault_account
-            .calculate_deposit(protocol_idx, self.vault_input_token_account.amount)
+    /// Compute the amount to deposit into the protocol, given the reserve total supply
+    pub fn amount_to_deposit(&self, protocol_idx: usize, reserve_supply: u64) -> Result<u64> {
+        let available_amount = self
+            .vault_account
+            .available_idle(self.vault_input_token_account.amount);
+        let amount = self
+            .vault_account
+            .calculate_deposit(protocol_idx, available_amount)?;
+        let amount = clip_to_reserve_share(
+            self.vault_account.key(),
+            &self.vault_account,
+            protocol_idx,
+            amount,
+            reserve_supply,
+        )?;
+        require!(amount > 0, ErrorCode::InvalidProtocolDeposit);
+        Ok(amount)
+    }
+}
+
+/// Clip the amount to deposit so that the vault does not own more than the maximum share of the
+/// protocol reserve, reporting the clipped amount
+pub fn clip_to_reserve_share(
+    vault_key: Pubkey,
+    vault_account: &VaultAccount,
+    protocol_idx: usize,
+    amount: u64,
+    reserve_supply: u64,
+) -> Result<u64> {
+    let max_amount = vault_account.max_reserve_deposit(protocol_idx, reserve_supply)?;
+    if amount <= max_amount {
+        return Ok(amount);
     }
+
+    emit!(ReserveShareClippedEvent {
+        vault: vault_key,
+        protocol_id: vault_account.protocols[protocol_idx].protocol_id,
+        amount: max_amount,
+        clipped_amount: amount - max_amount,
+    });
+
+    Ok(max_amount)
 }
@@ -1,6 +1,6 @@
 use crate::error::ErrorCode;
 use crate::protocols::Protocols;
-use crate::vault::ProtocolData;
+use crate::vault::{ProtocolData, ReserveRates};
 use crate::VaultAccount;
 use crate::VAULT_ACCOUNT_SEED;
 use anchor_lang::prelude::*;
@@ -28,6 +28,9 @@ pub trait ProtocolRewards<'info> {
     /// Return a mutable refrence of the data
     fn protocol_data_as_mut(&mut self, protocol_idx: usize) -> &mut ProtocolData;
 
+    /// Snapshot of the protocol reserve supply and borrow rate curve
+    fn reserve_rates(&self) -> Result<ReserveRates>;
+
     /// Compute the maximam withdrawable units
     fn max_withdrawable(&self) -> Result<u64>;
 }
@@ -38,12 +41,18 @@ pub fn handler<'info, T: ProtocolRewards<'info>>(
     protocol: Protocols,
 ) -> Result<()> {
     let protocol_idx = ctx.accounts.protocol_position(protocol)?;
-    let protocol_id: u8 = (protocol as usize).try_into().unwrap();
     let token = ctx.accounts.input_mint_pubkey();
 
     let tvl = ctx.accounts.max_withdrawable()?;
+    let reserve_rates = ctx.accounts.reserve_rates()?;
 
     let protocol_data = ctx.accounts.protocol_data_as_mut(protocol_idx);
+    protocol_data.reserve_rates = reserve_rates;
+    update_rewards(protocol_data, token, tvl)
+}
+
+/// Update the protocol rewards given its current TVL
+pub fn update_rewards(protocol_data: &mut ProtocolData, token: Pubkey, tvl: u64) -> Result<()> {
     let rewards: i64 = i64::try_from(tvl)
         .unwrap()
         .checked_sub(i64::try_from(protocol_data.amount).unwrap())
@@ -62,7 +71,7 @@ pub fn handler<'info, T: ProtocolRewards<'info>>(
         .map_err(|_| ErrorCode::MathOverflow)?;
 
     emit!(ProtocolRewardsEvent {
-        protocol_id,
+        protocol_id: protocol_data.protocol_id,
         token,
         rewards: protocol_data.rewards.amount,
         lamports: deposited_lamports,
@@ -1,6 +1,6 @@
 use crate::error::ErrorCode;
 use crate::protocols::Protocols;
-use crate::vault::{ProtocolData, VaultAccount};
+use crate::vault::{LpPrice, ProtocolData, VaultAccount};
 use crate::VAULT_ACCOUNT_SEED;
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{instruction::Instruction, sysvar};
@@ -42,42 +42,62 @@ pub fn handler<'info, T: ProtocolWithdraw<'info>>(
     let protocol_idx = ctx.accounts.protocol_position(protocol)?;
 
     let AmountWithCaller { mut amount, caller } = ctx.accounts.get_amount(protocol_idx)?;
-    if !ctx.accounts.protocol_data_as_mut(protocol_idx).is_active() && caller == Caller::Bot {
+    if !ctx.accounts.protocol_data_as_mut(protocol_idx).is_active() || caller == Caller::User {
         amount = std::cmp::min(amount, ctx.accounts.max_liquidity()?);
     }
+    if caller == Caller::User {
+        // The rest of the shortfall is left to the next protocol withdrawals of the chain
+        let deposited_amount = ctx.accounts.protocol_data_as_mut(protocol_idx).amount;
+        amount = std::cmp::min(amount, deposited_amount);
+    }
+
+    // The user withdrawal is already covered by the previous withdrawals of the chain
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let amount_diff = withdraw_liquidity(ctx.accounts, protocol_idx, amount)?;
+
+    ctx.accounts
+        .protocol_data_as_mut(protocol_idx)
+        .update_after_withdraw(amount_diff)?;
+
+    Ok(())
+}
 
-    let mut lp_amount = ctx.accounts.liquidity_to_collateral(amount)?;
+/// Withdraw the amount of liquidity from the protocol into the vault input token account,
+/// returning the amount actually received
+pub fn withdraw_liquidity<'info, T: ProtocolWithdraw<'info>>(
+    accounts: &mut T,
+    protocol_idx: usize,
+    amount: u64,
+) -> Result<u64> {
+    let mut lp_amount = accounts.liquidity_to_collateral(amount)?;
 
     // Add 1 as due to rounding. Otherwise it might happens that there wasn't enough funds
     // withdrawn from the protocol
-    if amount < ctx.accounts.protocol_data_as_mut(protocol_idx).amount {
+    if amount < accounts.protocol_data_as_mut(protocol_idx).amount {
         lp_amount = lp_amount
             .checked_add(1)
             .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
     }
 
     let amount_before = {
-        let input_token_account = ctx.accounts.input_token_account_as_mut();
+        let input_token_account = accounts.input_token_account_as_mut();
         input_token_account.amount
     };
 
-    ctx.accounts.cpi_withdraw(lp_amount)?;
+    accounts.cpi_withdraw(lp_amount)?;
 
     let amount_after = {
-        let input_token_account = ctx.accounts.input_token_account_as_mut();
+        let input_token_account = accounts.input_token_account_as_mut();
         input_token_account.reload()?;
         input_token_account.amount
     };
 
-    let amount_diff = amount_after
+    amount_after
         .checked_sub(amount_before)
-        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
-
-    ctx.accounts
-        .protocol_data_as_mut(protocol_idx)
-        .update_after_withdraw(amount_diff)?;
-
-    Ok(())
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))
 }
 
 /// Amount to withdraw and who call it
@@ -116,15 +136,24 @@ pub struct GenericWithdrawAccounts<'info> {
 
 impl<'info> GenericWithdrawAccounts<'info> {
     /// Compute the amount to withdraw from the protocol depending on whether the instruction comes
-    /// from the bot or from a user, assuming for the latter that the following ix corresponds
-    /// either to the `withdraw` or the `close_withdraw_ticket` one
+    /// from the bot or from a user, assuming for the latter that the following ixs correspond to a
+    /// chain of protocol withdrawals ended by one of the `withdraw` variants or by
+    /// `fill_withdraw_epoch`
     pub fn amount_to_withdraw(&self, protocol_idx: usize) -> Result<AmountWithCaller> {
-        if let Some(amount) = self.read_amount_from_next_ix()? {
+        if let Some(amount) = self.read_amount_from_next_ixs()? {
+            require!(
+                !self.vault_account.pause_flags().withdraw,
+                ErrorCode::OnPaused
+            );
             Ok(AmountWithCaller {
                 amount,
                 caller: Caller::User,
             })
         } else {
+            require!(
+                !self.vault_account.pause_flags().rebalance,
+                ErrorCode::OnPaused
+            );
             Ok(AmountWithCaller {
                 amount: self.vault_account.calculate_withdraw(protocol_idx)?,
                 caller: Caller::Bot,
@@ -132,39 +161,68 @@ impl<'info> GenericWithdrawAccounts<'info> {
         }
     }
 
-    /// Read the amount to withdraw from the next instruction
-    fn read_amount_from_next_ix(&self) -> Result<Option<u64>> {
-        if let Ok(next_ix) = sysvar::instructions::get_instruction_relative(1, &self.instructions) {
-            let lp_amount = read_amount_from_deserialized_ix(&next_ix)?;
-
-            let amount = self
+    /// Read the amount still missing for the user withdrawal which ends the chain of protocol
+    /// withdrawals following this instruction, if any
+    fn read_amount_from_next_ixs(&self) -> Result<Option<u64>> {
+        let mut relative_idx = 1;
+        while let Ok(next_ix) =
+            sysvar::instructions::get_instruction_relative(relative_idx, &self.instructions)
+        {
+            if is_protocol_withdraw_ix(&next_ix) {
+                relative_idx += 1;
+                continue;
+            }
+
+            let amount = read_amount_from_deserialized_ix(
+                &next_ix,
+                &self.vault_account.withdraw_lp_price()?,
+            )?;
+
+            // The amounts requested by the previous protocol withdrawals of the chain are already
+            // in the vault, so only the shortfall is withdrawn
+            let available_amount = self
                 .vault_account
-                .previous_lp_price
-                .lp_to_token(lp_amount)?;
-
-            let vault_token_amount = self.vault_input_token_account.amount;
-            require!(amount > vault_token_amount, ErrorCode::InvalidInstructions);
-
-            Ok(Some(
-                amount
-                    .checked_sub(vault_token_amount)
-                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
-            ))
-        } else {
-            Ok(None)
+                .available_idle(self.vault_input_token_account.amount);
+            return Ok(Some(amount.saturating_sub(available_amount)));
         }
+
+        Ok(None)
     }
 }
 
 /// Anchor generated sighash
 const IX_WITHDRAW_SIGHASH: [u8; 8] = [183, 18, 70, 156, 148, 109, 161, 34];
-const IX_CLOSE_WITHDRAW_TICKET_SIGHASH: [u8; 8] = [59, 115, 209, 162, 26, 58, 153, 83];
+const IX_WITHDRAW_EXACT_TOKENS_SIGHASH: [u8; 8] = [55, 42, 61, 249, 227, 171, 4, 191];
+const IX_WITHDRAW_TO_NATIVE_SIGHASH: [u8; 8] = [193, 54, 186, 68, 45, 100, 7, 131];
+const IX_FILL_WITHDRAW_EPOCH_SIGHASH: [u8; 8] = [32, 255, 13, 79, 236, 82, 100, 28];
+
+/// Anchor generated sighash of the protocol withdraw instructions
+const IX_PROTOCOL_WITHDRAW_SIGHASHES: [[u8; 8]; 5] = [
+    [238, 144, 170, 199, 21, 72, 155, 36],
+    [79, 150, 252, 223, 0, 180, 89, 2],
+    [77, 17, 83, 137, 58, 151, 199, 113],
+    [226, 223, 151, 183, 78, 26, 0, 44],
+    [145, 193, 80, 186, 173, 38, 60, 185],
+];
 
 /// Instruction data length (sighash + args)
-const IX_WITHDRAW_DATA_LEN: usize = 8 + 8;
-const IX_CLOSE_WITHDRAW_TICKET_DATA_LEN: usize = 8 + 1 + 8;
+const IX_WITHDRAW_DATA_LEN: usize = 8 + 8 + 8;
+const IX_WITHDRAW_EXACT_TOKENS_DATA_LEN: usize = 8 + 8 + 8;
+const IX_WITHDRAW_TO_NATIVE_DATA_LEN: usize = 8 + 8 + 8;
+const IX_FILL_WITHDRAW_EPOCH_DATA_LEN: usize = 8 + 8;
+
+/// Check whether the instruction is one of the protocol withdrawals
+fn is_protocol_withdraw_ix(ix: &Instruction) -> bool {
+    ix.program_id == crate::ID
+        && ix.data.len() == 8
+        && IX_PROTOCOL_WITHDRAW_SIGHASHES
+            .iter()
+            .any(|sighash| sighash[..] == ix.data[..])
+}
 
-fn read_amount_from_deserialized_ix(ix: &Instruction) -> Result<u64> {
+/// Read the input token amount withdrawn by the instruction, using the given LP price for the
+/// instructions defined by an LP amount
+fn read_amount_from_deserialized_ix(ix: &Instruction, lp_price: &LpPrice) -> Result<u64> {
     require!(
         ix.program_id == crate::ID && ix.data.len() > 8,
         ErrorCode::InvalidInstructions
@@ -187,19 +245,44 @@ fn read_amount_from_deserialized_ix(ix: &Instruction) -> Result<u64> {
 
             let ix = instruction::Withdraw::deserialize(&mut &ix.data[8..])
                 .map_err(|_| ErrorCode::InvalidInstructions)?;
-            let instruction::Withdraw { lp_amount } = ix;
-            Ok(lp_amount)
+            let instruction::Withdraw { lp_amount, .. } = ix;
+            lp_price.lp_to_token(lp_amount)
+        }
+        IX_WITHDRAW_EXACT_TOKENS_SIGHASH => {
+            require!(
+                ix.data.len() == IX_WITHDRAW_EXACT_TOKENS_DATA_LEN,
+                ErrorCode::InvalidInstructions
+            );
+
+            let ix = instruction::WithdrawExactTokens::deserialize(&mut &ix.data[8..])
+                .map_err(|_| ErrorCode::InvalidInstructions)?;
+            let instruction::WithdrawExactTokens { amount, .. } = ix;
+            // Including the 1 lamport lost due precision errors
+            amount
+                .checked_add(1)
+                .ok_or_else(|| error!(ErrorCode::MathOverflow))
+        }
+        IX_WITHDRAW_TO_NATIVE_SIGHASH => {
+            require!(
+                ix.data.len() == IX_WITHDRAW_TO_NATIVE_DATA_LEN,
+                ErrorCode::InvalidInstructions
+            );
+
+            let ix = instruction::WithdrawToNative::deserialize(&mut &ix.data[8..])
+                .map_err(|_| ErrorCode::InvalidInstructions)?;
+            let instruction::WithdrawToNative { lp_amount, .. } = ix;
+            lp_price.lp_to_token(lp_amount)
         }
-        IX_CLOSE_WITHDRAW_TICKET_SIGHASH => {
+        IX_FILL_WITHDRAW_EPOCH_SIGHASH => {
             require!(
-                ix.data.len() == IX_CLOSE_WITHDRAW_TICKET_DATA_LEN,
+                ix.data.len() == IX_FILL_WITHDRAW_EPOCH_DATA_LEN,
                 ErrorCode::InvalidInstructions
             );
 
-            let ix = instruction::CloseWithdrawTicket::deserialize(&mut &ix.data[8..])
+            let ix = instruction::FillWithdrawEpoch::deserialize(&mut &ix.data[8..])
                 .map_err(|_| ErrorCode::InvalidInstructions)?;
-            let instruction::CloseWithdrawTicket { lp_amount, .. } = ix;
-            Ok(lp_amount)
+            let instruction::FillWithdrawEpoch { amount } = ix;
+            Ok(amount)
         }
         _ => err!(ErrorCode::InvalidInstructions),
     }
@@ -0,0 +1,254 @@
+use crate::check_hash::CheckHash;
+use crate::error::ErrorCode;
+use crate::instructions::protocol_deposit::{clip_to_reserve_share, ProtocolDeposit};
+use crate::instructions::protocol_withdraw::{withdraw_liquidity, ProtocolWithdraw};
+use crate::protocols::{
+    francium::{FranciumDeposit, FranciumWithdraw},
+    port::{PortDeposit, PortWithdraw},
+    solend::{SolendDeposit, SolendWithdraw},
+    tulip::{TulipDeposit, TulipWithdraw},
+    Protocols,
+};
+use crate::vault::VaultAccount;
+use crate::VAULT_ACCOUNT_SEED;
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+#[derive(Accounts)]
+pub struct Rebalance<'info> {
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = vault_account.input_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_input_token_account: Account<'info, TokenAccount>,
+}
+
+/// Move the vault funds towards the protocol weights. The remaining accounts are the accounts of
+/// the `*_withdraw` instruction of every protocol, in the order of the vault protocols, followed
+/// by the accounts of the `*_deposit` instruction of every protocol, in the same order
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, Rebalance<'info>>) -> Result<()> {
+    let program_id = ctx.program_id;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let vault_key = ctx.accounts.vault_account.key();
+    let protocols = protocol_ids(&ctx.accounts.vault_account)?;
+
+    for (protocol_idx, protocol) in protocols.iter().enumerate() {
+        check_vault_key(remaining_accounts, &vault_key)?;
+        let vault_account = &mut ctx.accounts.vault_account;
+        match protocol {
+            Protocols::Solend | Protocols::SolendStablePool => {
+                let mut accounts = SolendWithdraw::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_withdraw(&mut accounts, vault_account, *protocol, protocol_idx)?;
+            }
+            Protocols::Port => {
+                let mut accounts = PortWithdraw::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_withdraw(&mut accounts, vault_account, *protocol, protocol_idx)?;
+            }
+            Protocols::Tulip => {
+                let mut accounts = TulipWithdraw::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_withdraw(&mut accounts, vault_account, *protocol, protocol_idx)?;
+            }
+            Protocols::Francium => {
+                let mut accounts = FranciumWithdraw::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_withdraw(&mut accounts, vault_account, *protocol, protocol_idx)?;
+            }
+            Protocols::Mango => return err!(ErrorCode::InvalidProtocolId),
+        }
+    }
+
+    for (protocol_idx, protocol) in protocols.iter().enumerate() {
+        ctx.accounts.vault_input_token_account.reload()?;
+        check_vault_key(remaining_accounts, &vault_key)?;
+        let vault_account = &mut ctx.accounts.vault_account;
+        let available_amount =
+            vault_account.available_idle(ctx.accounts.vault_input_token_account.amount);
+        match protocol {
+            Protocols::Solend | Protocols::SolendStablePool => {
+                let accounts = SolendDeposit::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_deposit(
+                    &accounts,
+                    vault_key,
+                    vault_account,
+                    *protocol,
+                    protocol_idx,
+                    available_amount,
+                )?;
+            }
+            Protocols::Port => {
+                let accounts = PortDeposit::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_deposit(
+                    &accounts,
+                    vault_key,
+                    vault_account,
+                    *protocol,
+                    protocol_idx,
+                    available_amount,
+                )?;
+            }
+            Protocols::Tulip => {
+                let accounts = TulipDeposit::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_deposit(
+                    &accounts,
+                    vault_key,
+                    vault_account,
+                    *protocol,
+                    protocol_idx,
+                    available_amount,
+                )?;
+            }
+            Protocols::Francium => {
+                let accounts = FranciumDeposit::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    &[],
+                    &mut BTreeMap::new(),
+                )?;
+                rebalance_deposit(
+                    &accounts,
+                    vault_key,
+                    vault_account,
+                    *protocol,
+                    protocol_idx,
+                    available_amount,
+                )?;
+            }
+            Protocols::Mango => return err!(ErrorCode::InvalidProtocolId),
+        }
+    }
+
+    require!(remaining_accounts.is_empty(), ErrorCode::InvalidArraySize);
+
+    Ok(())
+}
+
+/// Protocols of the vault, in order
+fn protocol_ids(vault_account: &VaultAccount) -> Result<Vec<Protocols>> {
+    vault_account
+        .protocols
+        .iter()
+        .map(|protocol_data| {
+            usize::from(protocol_data.protocol_id)
+                .try_into()
+                .map_err(|_| error!(ErrorCode::InvalidProtocolId))
+        })
+        .collect()
+}
+
+/// Check the next protocol accounts, which start with the generic ones, belong to the vault
+fn check_vault_key(remaining_accounts: &[AccountInfo], vault_key: &Pubkey) -> Result<()> {
+    let vault_account_info = remaining_accounts
+        .first()
+        .ok_or_else(|| error!(ErrorCode::InvalidArraySize))?;
+    require!(
+        vault_account_info.key == vault_key,
+        ErrorCode::InvalidVaultAccount
+    );
+    Ok(())
+}
+
+/// Withdraw the amount exceeding the protocol target, if any. The protocol accounts are only used
+/// to perform the CPI, while the protocol data is updated in the vault account of the instruction
+fn rebalance_withdraw<'info, T: ProtocolWithdraw<'info> + CheckHash<'info>>(
+    accounts: &mut T,
+    vault_account: &mut VaultAccount,
+    protocol: Protocols,
+    protocol_idx: usize,
+) -> Result<()> {
+    accounts.check_hash(protocol)?;
+    require!(
+        accounts.protocol_position(protocol)? == protocol_idx,
+        ErrorCode::InvalidProtocolId
+    );
+
+    let amount = vault_account.deposit_excess(protocol_idx)?;
+    let amount = std::cmp::min(amount, accounts.max_liquidity()?);
+    if amount == 0 {
+        return Ok(());
+    }
+
+    // The accounts were deserialized before the previous withdrawals
+    accounts.input_token_account_as_mut().reload()?;
+    let amount_diff = withdraw_liquidity(accounts, protocol_idx, amount)?;
+
+    vault_account.protocols[protocol_idx].update_after_withdraw(amount_diff)
+}
+
+/// Deposit the amount missing to reach the protocol target, if any, limited by the available
+/// amount and the maximum reserve share
+fn rebalance_deposit<'info, T: ProtocolDeposit<'info> + CheckHash<'info>>(
+    accounts: &T,
+    vault_key: Pubkey,
+    vault_account: &mut VaultAccount,
+    protocol: Protocols,
+    protocol_idx: usize,
+    available_amount: u64,
+) -> Result<()> {
+    accounts.check_hash(protocol)?;
+    require!(
+        accounts.protocol_position(protocol)? == protocol_idx,
+        ErrorCode::InvalidProtocolId
+    );
+
+    let amount = std::cmp::min(
+        vault_account.deposit_shortfall(protocol_idx)?,
+        available_amount,
+    );
+    let amount = clip_to_reserve_share(
+        vault_key,
+        vault_account,
+        protocol_idx,
+        amount,
+        accounts.reserve_supply()?,
+    )?;
+    if amount == 0 {
+        return Ok(());
+    }
+
+    accounts.cpi_deposit(amount)?;
+
+    vault_account.protocols[protocol_idx].update_after_deposit(amount)
+}
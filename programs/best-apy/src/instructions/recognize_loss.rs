@@ -0,0 +1,67 @@
+use crate::config::ConfigAccount;
+use crate::vault::{LpPrice, VaultAccount};
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::Mint;
+
+#[event]
+pub struct RecognizeLossEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    loss: u64,
+    previous_price: LpPrice,
+    current_price: LpPrice,
+}
+
+#[derive(Accounts)]
+pub struct RecognizeLoss<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bumps.lp_token_mint
+    )]
+    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
+}
+
+impl<'info> RecognizeLoss<'info> {
+    fn current_lp_price(&self) -> LpPrice {
+        LpPrice {
+            total_tokens: self.vault_account.current_tvl,
+            minted_tokens: self.vault_lp_token_mint_pubkey.supply,
+        }
+    }
+}
+
+/// Book the realized loss in the vault TVL and reset the LP price, so that users keep operating
+/// at the lower price
+pub fn handler(ctx: Context<RecognizeLoss>, loss: u64) -> Result<()> {
+    let previous_price = ctx.accounts.vault_account.previous_lp_price;
+
+    ctx.accounts.vault_account.recognize_loss(loss)?;
+
+    let current_price = ctx.accounts.current_lp_price();
+    ctx.accounts.vault_account.previous_lp_price = current_price;
+
+    emit!(RecognizeLossEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        loss,
+        previous_price,
+        current_price,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use crate::check_hash::CHECKHASH_BYTES;
+use crate::error::ErrorCode;
+use crate::instructions::protocol_rewards::update_rewards;
+use crate::protocols::{francium, port, solend, tulip, Protocols};
+use crate::vault::VaultAccount;
+use crate::VAULT_ACCOUNT_SEED;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_spl::token::TokenAccount;
+use std::convert::TryInto;
+
+/// Number of remaining accounts required to compute the TVL of each protocol
+const TVL_ACCOUNTS_PER_PROTOCOL: usize = 2;
+
+#[derive(Accounts)]
+pub struct RefreshAllTvl<'info> {
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Update the rewards and reserve snapshot of every protocol in the vault. The remaining accounts
+/// are given in the order of the vault protocols, as pairs of reserve (or lending pool) and vault
+/// collateral token account
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, RefreshAllTvl<'info>>) -> Result<()> {
+    let vault_key = ctx.accounts.vault_account.key();
+    let input_mint_pubkey = ctx.accounts.vault_account.input_mint_pubkey;
+    let protocols = &mut ctx.accounts.vault_account.protocols;
+
+    require!(
+        ctx.remaining_accounts.len() == protocols.len() * TVL_ACCOUNTS_PER_PROTOCOL,
+        ErrorCode::InvalidArraySize
+    );
+
+    for (protocol_data, accounts) in protocols
+        .iter_mut()
+        .zip(ctx.remaining_accounts.chunks(TVL_ACCOUNTS_PER_PROTOCOL))
+    {
+        let reserve = &accounts[0];
+        let collateral_token_account_info = &accounts[1];
+
+        let hash = hashv(&[
+            reserve.key.as_ref(),
+            collateral_token_account_info.key.as_ref(),
+        ]);
+        require!(
+            hash.to_bytes()[..CHECKHASH_BYTES] == protocol_data.hash_pubkey.hash_tvl,
+            ErrorCode::InvalidHash
+        );
+
+        let collateral_token_account: Account<TokenAccount> =
+            Account::try_from(collateral_token_account_info)?;
+        require!(
+            collateral_token_account.owner == vault_key,
+            ErrorCode::InvalidOwner
+        );
+
+        let protocol: Protocols = usize::from(protocol_data.protocol_id)
+            .try_into()
+            .map_err(|_| error!(ErrorCode::InvalidProtocolId))?;
+        let (tvl, reserve_rates) = match protocol {
+            Protocols::Solend | Protocols::SolendStablePool => (
+                solend::tvl(reserve, &collateral_token_account, &input_mint_pubkey)?,
+                solend::reserve_rates(reserve)?,
+            ),
+            Protocols::Port => (
+                port::tvl(reserve, &collateral_token_account, &input_mint_pubkey)?,
+                port::reserve_rates(reserve)?,
+            ),
+            Protocols::Tulip => (
+                tulip::tvl(reserve, &collateral_token_account, &input_mint_pubkey)?,
+                tulip::reserve_rates(reserve)?,
+            ),
+            Protocols::Francium => (
+                francium::tvl(reserve, &collateral_token_account, &input_mint_pubkey)?,
+                francium::reserve_rates(reserve)?,
+            ),
+            Protocols::Mango => return err!(ErrorCode::InvalidProtocolId),
+        };
+
+        protocol_data.reserve_rates = reserve_rates;
+        update_rewards(protocol_data, input_mint_pubkey, tvl)?;
+    }
+
+    Ok(())
+}
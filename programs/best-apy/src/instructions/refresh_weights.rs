@@ -5,18 +5,16 @@ use crate::{VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
 use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
-use std::convert::{TryFrom, TryInto};
 
 /// Maximum elapsed slots for computing the protocols TVL
 const MAX_ELAPSED_SLOTS_FOR_TVL: u64 = 30;
 
-/// Protocol fee
-const FEE: u128 = 0; // in per mil
-
 #[event]
 pub struct RefreshWeightsEvent {
     token: Pubkey,
     current_price: LpPrice,
+    performance_fee_lp: u64,
+    management_fee_lp: u64,
 }
 
 #[derive(Accounts)]
@@ -58,53 +56,67 @@ impl<'info> RefreshWeights<'info> {
         )
     }
 
-    /// Mint LP tokens to the treasury account in order to take the fees (if any)
-    fn mint_or_zero_fees(&self) -> Result<bool> {
+    /// Mint LP tokens to the treasury account in order to take the management fees accrued
+    /// during the elapsed time (if any)
+    fn mint_management_fees(&self, elapsed_time: i64) -> Result<u64> {
+        if self.vault_account.current_tvl == 0 {
+            return Ok(0);
+        }
+
+        let lp_fee = self
+            .vault_account
+            .fees
+            .management_fee_lp(elapsed_time, self.vault_lp_token_mint_pubkey.supply)?;
+
+        if lp_fee > 0 {
+            let seeds = generate_seeds!(self.vault_account);
+            let signer = &[&seeds[..]];
+            token::mint_to(self.mint_lps_to_treasury_ctx().with_signer(signer), lp_fee)?;
+        }
+
+        Ok(lp_fee)
+    }
+
+    /// Mint LP tokens to the treasury account in order to take the performance fees (if any)
+    fn mint_or_zero_fees(&self) -> Result<(bool, u64)> {
         let mut tvl_is_stale = false;
+        let mut minted_lp = 0;
 
         let rewards = self.vault_account.rewards_sum;
         if rewards > 0 {
-            if FEE == 0 {
+            if self.vault_account.fees.performance_fee_bps == 0 {
                 tvl_is_stale = true;
             } else {
-                let lp_fee: u64 = FEE
-                    .checked_mul(rewards as u128)
-                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                    .checked_mul(self.vault_lp_token_mint_pubkey.supply as u128)
-                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                    .checked_div(
-                        (self.vault_account.current_tvl as u128)
-                            .checked_add(
-                                (1000 - FEE)
-                                    .checked_mul(rewards as u128)
-                                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                                    .checked_div(1000)
-                                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
-                            )
-                            .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
-                    )
-                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                    .checked_div(1000)
-                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                    .try_into()
-                    .map_err(|_| ErrorCode::MathOverflow)?;
+                let lp_fee = self.vault_account.fees.performance_fee_lp(
+                    rewards,
+                    self.vault_account.current_tvl,
+                    self.vault_lp_token_mint_pubkey.supply,
+                )?;
 
                 if lp_fee > 0 {
                     let seeds = generate_seeds!(self.vault_account);
                     let signer = &[&seeds[..]];
                     token::mint_to(self.mint_lps_to_treasury_ctx().with_signer(signer), lp_fee)?;
                     tvl_is_stale = true;
+                    minted_lp = lp_fee;
                 }
             }
         }
 
-        Ok(tvl_is_stale)
+        Ok((tvl_is_stale, minted_lp))
     }
 }
 
 /// Refresh the protocol weights
 pub fn handler(ctx: Context<RefreshWeights>) -> Result<()> {
     let current_time = Clock::get()?.unix_timestamp;
+    let elapsed_since_refresh = if ctx.accounts.vault_account.last_refresh_time != i64::default() {
+        current_time
+            .checked_sub(ctx.accounts.vault_account.last_refresh_time)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+    } else {
+        0
+    };
 
     if ctx.accounts.vault_account.refresh.min_elapsed_time != i64::default() {
         let elapsed_time = Clock::get()?
@@ -135,20 +147,9 @@ pub fn handler(ctx: Context<RefreshWeights>) -> Result<()> {
     }
 
     ctx.accounts.vault_account.last_refresh_time = current_time;
-    ctx.accounts.vault_account.rewards_sum = ctx
-        .accounts
-        .vault_account
-        .protocols
-        .iter()
-        .try_fold(
-            i64::try_from(ctx.accounts.vault_account.rewards_sum).unwrap(),
-            |acc, protocol| acc.checked_add(protocol.rewards.amount),
-        )
-        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-        .try_into()
-        .unwrap();
+    ctx.accounts.vault_account.accumulate_protocol_rewards()?;
 
-    ctx.accounts.vault_account.update_protocol_weights()?;
+    ctx.accounts.vault_account.refresh_protocol_weights()?;
     ctx.accounts
         .vault_account
         .protocols
@@ -158,9 +159,17 @@ pub fn handler(ctx: Context<RefreshWeights>) -> Result<()> {
             protocol.rewards.reset_integral().unwrap();
         });
 
+    // Take the management fees before updating the price, so that the LP price does not decrease
+    // from the previous one
+    let management_fee_lp = ctx.accounts.mint_management_fees(elapsed_since_refresh)?;
+    if management_fee_lp > 0 {
+        ctx.accounts.vault_lp_token_mint_pubkey.reload()?;
+    }
+
     ctx.accounts.vault_account.previous_lp_price = ctx.accounts.current_lp_price();
 
-    if ctx.accounts.mint_or_zero_fees()? {
+    let (tvl_is_stale, performance_fee_lp) = ctx.accounts.mint_or_zero_fees()?;
+    if tvl_is_stale {
         ctx.accounts.vault_account.current_tvl = ctx
             .accounts
             .vault_account
@@ -174,6 +183,8 @@ pub fn handler(ctx: Context<RefreshWeights>) -> Result<()> {
     emit!(RefreshWeightsEvent {
         token: ctx.accounts.vault_account.input_mint_pubkey,
         current_price: ctx.accounts.current_lp_price(),
+        performance_fee_lp,
+        management_fee_lp,
     });
 
     Ok(())
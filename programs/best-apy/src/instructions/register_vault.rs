@@ -0,0 +1,82 @@
+use crate::config::ConfigAccount;
+use crate::registry::{RegistryAccount, RegistryEntry};
+use crate::vault::VaultAccount;
+use crate::vault_index::VaultIndexAccount;
+use crate::{
+    CONFIG_ACCOUNT_SEED, REGISTRY_SEED, VAULT_ACCOUNT_SEED, VAULT_INDEX_SEED,
+    VAULT_LP_TOKEN_MINT_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::Mint;
+
+#[derive(Accounts)]
+pub struct RegisterVault<'info> {
+    #[account(mut)]
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bumps.lp_token_mint
+    )]
+    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + VaultIndexAccount::SIZE,
+        seeds = [VAULT_INDEX_SEED, vault_account.input_mint_pubkey.as_ref()],
+        bump,
+    )]
+    pub vault_index_account: Box<Account<'info, VaultIndexAccount>>,
+    #[account(
+        init_if_needed,
+        payer = user_signer,
+        space = 8 + RegistryAccount::SIZE,
+        seeds = [REGISTRY_SEED],
+        bump,
+    )]
+    pub registry_account: Box<Account<'info, RegistryAccount>>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Register a vault created before the registry, recording the protocols already added to it,
+/// and add it to the index of its input mint if missing
+pub fn handler(ctx: Context<RegisterVault>) -> Result<()> {
+    let vault = &ctx.accounts.vault_account;
+
+    let vault_index = &mut ctx.accounts.vault_index_account;
+    if vault_index.input_mint_pubkey == Pubkey::default() {
+        vault_index.bump = *ctx.bumps.get("vault_index_account").unwrap();
+        vault_index.input_mint_pubkey = vault.input_mint_pubkey;
+    }
+    if !vault_index.contains(vault.seed_number) {
+        vault_index.insert(vault.seed_number)?;
+    }
+
+    let mut entry = RegistryEntry {
+        vault: vault.key(),
+        input_mint_pubkey: vault.input_mint_pubkey,
+        seed_number: vault.seed_number,
+        lp_token_mint_pubkey: ctx.accounts.vault_lp_token_mint_pubkey.key(),
+        ..RegistryEntry::default()
+    };
+    for protocol in vault.protocols.iter() {
+        entry.add_protocol(protocol.protocol_id)?;
+    }
+
+    let registry = &mut ctx.accounts.registry_account;
+    registry.bump = *ctx.bumps.get("registry_account").unwrap();
+    registry.register(entry)?;
+
+    Ok(())
+}
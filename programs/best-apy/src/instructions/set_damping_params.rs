@@ -0,0 +1,46 @@
+use crate::config::ConfigAccount;
+use crate::vault::{DampingParams, DeviationUnit, VaultAccount};
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetDampingParamsEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    max_weight_delta: u16,
+    min_deviation: u64,
+    min_deviation_unit: DeviationUnit,
+}
+
+#[derive(Accounts)]
+pub struct SetDampingParams<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the maximum change of the protocol weights on each refresh, and the minimum deviation of
+/// a protocol from its target for the rebalancing instructions to move funds
+pub fn handler(ctx: Context<SetDampingParams>, params: DampingParams) -> Result<()> {
+    params.validate()?;
+    ctx.accounts.vault_account.damping = params;
+
+    emit!(SetDampingParamsEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        max_weight_delta: params.max_weight_delta,
+        min_deviation: params.min_deviation,
+        min_deviation_unit: params.min_deviation_unit,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use crate::config::ConfigAccount;
+use crate::vault::{FeeParams, VaultAccount};
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetFeesEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    previous: FeeParams,
+    current: FeeParams,
+}
+
+#[derive(Accounts)]
+pub struct SetFees<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the vault fees
+pub fn handler(ctx: Context<SetFees>, fees: FeeParams) -> Result<()> {
+    fees.validate()?;
+
+    let previous = ctx.accounts.vault_account.fees;
+    ctx.accounts.vault_account.fees = fees;
+
+    emit!(SetFeesEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        previous,
+        current: fees,
+    });
+
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use crate::check_hash::CHECKHASH_BYTES;
+use crate::config::ConfigAccount;
 use crate::error::ErrorCode;
 use crate::protocols::Protocols;
 use crate::vault::VaultAccount;
-use crate::VAULT_ACCOUNT_SEED;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use std::convert::TryInto;
@@ -10,6 +11,11 @@ use std::convert::TryInto;
 #[derive(Accounts)]
 pub struct SetHashes<'info> {
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     #[account(
         mut,
         seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
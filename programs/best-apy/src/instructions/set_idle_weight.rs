@@ -0,0 +1,44 @@
+use crate::config::ConfigAccount;
+use crate::vault::VaultAccount;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetIdleWeightEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    previous: u32,
+    current: u32,
+}
+
+#[derive(Accounts)]
+pub struct SetIdleWeight<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the percentage of the TVL kept idle in the vault as withdrawal buffer. The protocol
+/// weights are applied over the rest of the TVL
+pub fn handler(ctx: Context<SetIdleWeight>, idle_weight: u32) -> Result<()> {
+    let previous = ctx.accounts.vault_account.idle_weight;
+    ctx.accounts.vault_account.set_idle_weight(idle_weight)?;
+
+    emit!(SetIdleWeightEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        previous,
+        current: idle_weight,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::config::ConfigAccount;
+use crate::vault::VaultAccount;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetMaxReserveShareEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    previous: u16,
+    current: u16,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxReserveShare<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the maximum share of a protocol reserve total supply owned by the vault (zero if
+/// uncapped). Deposits exceeding it are clipped
+pub fn handler(ctx: Context<SetMaxReserveShare>, max_reserve_share_bps: u16) -> Result<()> {
+    let previous = ctx.accounts.vault_account.max_reserve_share_bps;
+    ctx.accounts
+        .vault_account
+        .set_max_reserve_share(max_reserve_share_bps)?;
+
+    emit!(SetMaxReserveShareEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        previous,
+        current: max_reserve_share_bps,
+    });
+
+    Ok(())
+}
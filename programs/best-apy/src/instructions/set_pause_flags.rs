@@ -0,0 +1,54 @@
+use crate::config::{ConfigAccount, Role};
+use crate::error::ErrorCode;
+use crate::vault::{PauseFlags, VaultAccount};
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetPauseFlagsEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    previous: PauseFlags,
+    current: PauseFlags,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseFlags<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the operations paused in the vault
+pub fn handler(ctx: Context<SetPauseFlags>, flags: PauseFlags) -> Result<()> {
+    let previous = ctx.accounts.vault_account.pause_flags();
+
+    // The guardian is only allowed to pause further operations
+    if !ctx
+        .accounts
+        .config_account
+        .has_role(ctx.accounts.user_signer.key, Role::Admin)
+    {
+        require!(flags.contains(&previous), ErrorCode::UnauthorizedUser);
+    }
+
+    ctx.accounts.vault_account.set_pause_flags(flags);
+
+    emit!(SetPauseFlagsEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        previous,
+        current: flags,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use crate::config::ConfigAccount;
+use crate::error::ErrorCode;
+use crate::protocols::Protocols;
+use crate::vault::VaultAccount;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+use std::convert::TryInto;
+
+#[event]
+pub struct SetProtocolCapsEvent {
+    vault: Pubkey,
+    protocol_id: u8,
+    max_weight: u32,
+    max_amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolCaps<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the maximum weight and token amount of a protocol (zero if uncapped), limiting the
+/// current weights accordingly
+pub fn handler(
+    ctx: Context<SetProtocolCaps>,
+    protocol_id: u8,
+    max_weight: u32,
+    max_amount: u64,
+) -> Result<()> {
+    let protocol: Protocols = usize::from(protocol_id)
+        .try_into()
+        .map_err(|_| error!(ErrorCode::InvalidProtocolId))?;
+
+    let vault = &mut ctx.accounts.vault_account;
+    let protocol_idx = vault.protocol_position(protocol)?;
+    vault.protocols[protocol_idx].set_caps(max_weight, max_amount)?;
+    vault.cap_protocol_weights()?;
+
+    emit!(SetProtocolCapsEvent {
+        vault: vault.key(),
+        protocol_id,
+        max_weight,
+        max_amount,
+    });
+
+    Ok(())
+}
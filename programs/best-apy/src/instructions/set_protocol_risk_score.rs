@@ -0,0 +1,53 @@
+use crate::config::ConfigAccount;
+use crate::error::ErrorCode;
+use crate::protocols::Protocols;
+use crate::vault::VaultAccount;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+use std::convert::TryInto;
+
+#[event]
+pub struct SetProtocolRiskScoreEvent {
+    vault: Pubkey,
+    protocol_id: u8,
+    previous: u8,
+    current: u8,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolRiskScore<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the risk score of a protocol, used by the risk adjusted weight strategy on the next
+/// refreshes
+pub fn handler(ctx: Context<SetProtocolRiskScore>, protocol_id: u8, risk_score: u8) -> Result<()> {
+    let protocol: Protocols = usize::from(protocol_id)
+        .try_into()
+        .map_err(|_| error!(ErrorCode::InvalidProtocolId))?;
+
+    let vault = &mut ctx.accounts.vault_account;
+    let protocol_idx = vault.protocol_position(protocol)?;
+    let previous = vault.protocols[protocol_idx].risk_score;
+    vault.protocols[protocol_idx].set_risk_score(risk_score);
+
+    emit!(SetProtocolRiskScoreEvent {
+        vault: vault.key(),
+        protocol_id,
+        previous,
+        current: risk_score,
+    });
+
+    Ok(())
+}
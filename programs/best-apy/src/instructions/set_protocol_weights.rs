@@ -1,12 +1,18 @@
+use crate::config::ConfigAccount;
 use crate::error::ErrorCode;
 use crate::vault::{VaultAccount, WEIGHTS_SCALE};
-use crate::VAULT_ACCOUNT_SEED;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
 
 #[derive(Accounts)]
 pub struct SetProtocolWeights<'info> {
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     #[account(
         mut,
         seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
@@ -33,6 +39,16 @@ pub fn handler(ctx: Context<SetProtocolWeights>, weights: Vec<u32>) -> Result<()
         ErrorCode::InvalidWeights
     );
 
+    require!(
+        ctx.accounts
+            .vault_account
+            .protocols
+            .iter()
+            .zip(weights.iter())
+            .all(|(protocol, &weight)| protocol.max_weight == 0 || weight <= protocol.max_weight),
+        ErrorCode::InvalidWeights
+    );
+
     ctx.accounts
         .vault_account
         .protocols
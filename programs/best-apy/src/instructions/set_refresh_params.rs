@@ -1,10 +1,16 @@
+use crate::config::ConfigAccount;
 use crate::vault::{RefreshParams, VaultAccount};
-use crate::VAULT_ACCOUNT_SEED;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
 use anchor_lang::prelude::*;
 
 #[derive(Accounts)]
 pub struct SetRefreshParams<'info> {
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     #[account(
         mut,
         seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
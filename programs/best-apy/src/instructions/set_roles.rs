@@ -0,0 +1,33 @@
+use crate::config::ConfigAccount;
+use crate::CONFIG_ACCOUNT_SEED;
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetRolesEvent {
+    guardian: Pubkey,
+    strategist: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct SetRoles<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+}
+
+/// Set the guardian and strategist authorities
+pub fn handler(ctx: Context<SetRoles>, guardian: Pubkey, strategist: Pubkey) -> Result<()> {
+    ctx.accounts.config_account.guardian = guardian;
+    ctx.accounts.config_account.strategist = strategist;
+
+    emit!(SetRolesEvent {
+        guardian,
+        strategist
+    });
+
+    Ok(())
+}
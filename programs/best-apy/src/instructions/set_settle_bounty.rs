@@ -0,0 +1,43 @@
+use crate::config::ConfigAccount;
+use crate::vault::VaultAccount;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetSettleBountyEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    previous: u16,
+    current: u16,
+}
+
+#[derive(Accounts)]
+pub struct SetSettleBounty<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the bounty paid to the keepers settling withdrawal tickets
+pub fn handler(ctx: Context<SetSettleBounty>, bounty_bps: u16) -> Result<()> {
+    let previous = ctx.accounts.vault_account.settle_bounty_bps;
+    ctx.accounts.vault_account.set_settle_bounty(bounty_bps)?;
+
+    emit!(SetSettleBountyEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        previous,
+        current: bounty_bps,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use crate::config::ConfigAccount;
+use crate::vault::VaultAccount;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{Mint, TokenAccount};
+
+#[event]
+pub struct SetTreasuryEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    previous: Pubkey,
+    current: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bumps.lp_token_mint
+    )]
+    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        associated_token::mint = vault_lp_token_mint_pubkey,
+        associated_token::authority = dao_treasury_owner,
+    )]
+    pub dao_treasury_lp_token_account: Account<'info, TokenAccount>,
+    /// CHECK: any owner chosen by the admin
+    pub dao_treasury_owner: AccountInfo<'info>,
+}
+
+/// Set the treasury account receiving the vault fees
+pub fn handler(ctx: Context<SetTreasury>) -> Result<()> {
+    let previous = ctx.accounts.vault_account.dao_treasury_lp_token_account;
+    ctx.accounts.vault_account.dao_treasury_lp_token_account =
+        ctx.accounts.dao_treasury_lp_token_account.key();
+
+    emit!(SetTreasuryEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        previous,
+        current: ctx.accounts.vault_account.dao_treasury_lp_token_account,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use crate::config::ConfigAccount;
+use crate::vault::{VaultAccount, WeightStrategy};
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct SetWeightStrategyEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    previous: WeightStrategy,
+    current: WeightStrategy,
+}
+
+#[derive(Accounts)]
+pub struct SetWeightStrategy<'info> {
+    pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+}
+
+/// Set the algorithm used to update the protocol weights on the next refreshes
+pub fn handler(ctx: Context<SetWeightStrategy>, weight_strategy: WeightStrategy) -> Result<()> {
+    let previous = ctx.accounts.vault_account.weight_strategy;
+    ctx.accounts.vault_account.weight_strategy = weight_strategy;
+
+    emit!(SetWeightStrategyEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        previous,
+        current: weight_strategy,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use crate::error::ErrorCode;
+use crate::macros::generate_seeds;
+use crate::ticket::{WithdrawEpochAccount, WithdrawTicketAccount};
+use crate::vault::VaultAccount;
+use crate::{
+    VAULT_ACCOUNT_SEED, VAULT_TICKET_MINT_SEED, WITHDRAW_EPOCH_SEED, WITHDRAW_TICKET_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
+
+#[event]
+pub struct SettleWithdrawTicketEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    keeper: Pubkey,
+    epoch: u64,
+    amount: u64,
+    bounty: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(bump_user: u8)]
+pub struct SettleWithdrawTicket<'info> {
+    pub keeper_signer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = keeper_input_token_account.mint == vault_account.input_mint_pubkey
+    )]
+    pub keeper_input_token_account: Account<'info, TokenAccount>,
+    /// CHECK: owner of the ticket, enforced by the ticket seeds
+    pub user: AccountInfo<'info>,
+    #[account(
+        mut,
+        associated_token::mint = vault_account.input_mint_pubkey,
+        associated_token::authority = user,
+    )]
+    pub user_input_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = vault_user_ticket_account.owner == vault_account.key(),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_ticket_mint_pubkey.key().as_ref(), user.key().as_ref()],
+        bump = bump_user
+    )]
+    pub vault_user_ticket_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        mut,
+        constraint = vault_ticket_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_TICKET_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bump_ticket_mint
+    )]
+    pub vault_ticket_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = vault_account.input_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_input_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_TICKET_SEED, vault_account.key().as_ref(), user.key().as_ref()],
+        bump = withdraw_ticket_account.bump
+    )]
+    pub withdraw_ticket_account: Box<Account<'info, WithdrawTicketAccount>>,
+    #[account(
+        mut,
+        seeds = [WITHDRAW_EPOCH_SEED, vault_account.key().as_ref(), &withdraw_ticket_account.epoch.to_le_bytes()],
+        bump = withdraw_epoch_account.bump
+    )]
+    pub withdraw_epoch_account: Box<Account<'info, WithdrawEpochAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> SettleWithdrawTicket<'info> {
+    fn transfer_from_vault_ctx(
+        &self,
+        to: &Account<'info, TokenAccount>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.vault_input_token_account.to_account_info(),
+                to: to.to_account_info(),
+                authority: self.vault_account.to_account_info(),
+            },
+        )
+    }
+
+    fn burn_ticket_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Burn {
+                mint: self.vault_ticket_mint_pubkey.to_account_info(),
+                from: self.vault_user_ticket_account.to_account_info(),
+                authority: self.vault_account.to_account_info(),
+            },
+        )
+    }
+}
+
+/// Settle a withdrawal ticket on behalf of its owner, paying the share of the amount filled so
+/// far in the ticket epoch to the owner input token ATA. The vault settle bounty is deducted from
+/// the paid amount and sent to the keeper. A filled ticket is emptied even if the rounded down
+/// payout is zero
+pub fn handler(ctx: Context<SettleWithdrawTicket>, _bump_user: u8) -> Result<()> {
+    let amount = ctx
+        .accounts
+        .withdraw_epoch_account
+        .claimable_amount(&ctx.accounts.withdraw_ticket_account)?;
+    let epoch_filled = ctx.accounts.withdraw_epoch_account.is_filled();
+
+    require!(amount > 0 || epoch_filled, ErrorCode::InvalidZeroWithdraw);
+
+    let bounty = ctx.accounts.vault_account.settle_bounty(amount)?;
+    let amount_to_user = amount
+        .checked_sub(bounty)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+    let seeds = generate_seeds!(ctx.accounts.vault_account);
+    let signer = &[&seeds[..]];
+
+    if epoch_filled {
+        token::burn(
+            ctx.accounts.burn_ticket_ctx().with_signer(signer),
+            ctx.accounts.withdraw_ticket_account.lp_amount,
+        )?;
+    }
+    if amount_to_user > 0 {
+        token::transfer(
+            ctx.accounts
+                .transfer_from_vault_ctx(&ctx.accounts.user_input_token_account)
+                .with_signer(signer),
+            amount_to_user,
+        )?;
+    }
+    if bounty > 0 {
+        token::transfer(
+            ctx.accounts
+                .transfer_from_vault_ctx(&ctx.accounts.keeper_input_token_account)
+                .with_signer(signer),
+            bounty,
+        )?;
+    }
+
+    let dust = ctx
+        .accounts
+        .withdraw_epoch_account
+        .claim(&ctx.accounts.withdraw_ticket_account, amount)?;
+    ctx.accounts
+        .withdraw_ticket_account
+        .claim(amount, epoch_filled)?;
+    ctx.accounts.vault_account.pay_ticket(amount, dust)?;
+
+    emit!(SettleWithdrawTicketEvent {
+        vault: ctx.accounts.vault_account.key(),
+        user: ctx.accounts.user.key(),
+        keeper: ctx.accounts.keeper_signer.key(),
+        epoch: ctx.accounts.withdraw_ticket_account.epoch,
+        amount: amount_to_user,
+        bounty,
+    });
+
+    Ok(())
+}
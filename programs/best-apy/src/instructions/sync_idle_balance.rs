@@ -0,0 +1,52 @@
+use crate::error::ErrorCode;
+use crate::vault::VaultAccount;
+use crate::VAULT_ACCOUNT_SEED;
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use std::convert::TryInto;
+
+#[event]
+pub struct SyncIdleBalanceEvent {
+    vault: Pubkey,
+    token: Pubkey,
+    difference: i64,
+    rewards_sum: u64,
+    pending_loss: u64,
+}
+
+#[derive(Accounts)]
+pub struct SyncIdleBalance<'info> {
+    #[account(
+        mut,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        associated_token::mint = vault_account.input_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_input_token_account: Account<'info, TokenAccount>,
+}
+
+/// Reconcile the vault holdings with its TVL, booking donations and leftovers as rewards and
+/// shortfalls as pending loss
+pub fn handler(ctx: Context<SyncIdleBalance>) -> Result<()> {
+    let balance = ctx.accounts.vault_input_token_account.amount;
+    let difference = ctx
+        .accounts
+        .vault_account
+        .reconcile_holdings(balance)?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?;
+
+    emit!(SyncIdleBalanceEvent {
+        vault: ctx.accounts.vault_account.key(),
+        token: ctx.accounts.vault_account.input_mint_pubkey,
+        difference,
+        rewards_sum: ctx.accounts.vault_account.rewards_sum,
+        pending_loss: ctx.accounts.vault_account.pending_loss,
+    });
+
+    Ok(())
+}
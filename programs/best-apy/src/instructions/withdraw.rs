@@ -32,18 +32,22 @@ pub struct Withdraw<'info> {
         associated_token::authority = vault_account,
     )]
     pub vault_input_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = vault_account.dao_treasury_lp_token_account)]
+    pub dao_treasury_lp_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
 }
 
 impl<'info> Withdraw<'info> {
-    fn current_lp_price(&self) -> LpPrice {
+    pub(crate) fn current_lp_price(&self) -> LpPrice {
         LpPrice {
             total_tokens: self.vault_account.current_tvl,
             minted_tokens: self.vault_lp_token_mint_pubkey.supply,
         }
     }
 
-    fn transfer_from_vault_to_user_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+    pub(crate) fn transfer_from_vault_to_user_ctx(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         CpiContext::new(
             self.token_program.to_account_info(),
             Transfer {
@@ -54,7 +58,7 @@ impl<'info> Withdraw<'info> {
         )
     }
 
-    fn burn_user_lps_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+    pub(crate) fn burn_user_lps_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
         CpiContext::new(
             self.token_program.to_account_info(),
             Burn {
@@ -64,28 +68,70 @@ impl<'info> Withdraw<'info> {
             },
         )
     }
+
+    pub(crate) fn transfer_lps_from_user_to_treasury_ctx(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.user_lp_token_account.to_account_info(),
+                to: self.dao_treasury_lp_token_account.to_account_info(),
+                authority: self.user_signer.to_account_info(),
+            },
+        )
+    }
 }
 
-/// Withdraw the required input tokens from the vault and send them back to the user
-pub fn handler(ctx: Context<Withdraw>, lp_amount: u64) -> Result<()> {
+/// Withdraw the required input tokens from the vault and send them back to the user, reverting
+/// if the user would receive less than `min_tokens_out`
+pub fn handler(ctx: Context<Withdraw>, lp_amount: u64, min_tokens_out: u64) -> Result<()> {
     let current_price = ctx.accounts.current_lp_price();
     let previous_price = ctx.accounts.vault_account.previous_lp_price;
 
-    if previous_price != LpPrice::default() {
+    if previous_price.minted_tokens != 0 {
         require!(current_price >= previous_price, ErrorCode::InvalidLpPrice);
     }
 
-    // Use previous value of LP in order to avoid depositors.
+    let lp_fee = ctx.accounts.vault_account.fees.withdraw_fee_lp(lp_amount)?;
+    let lp_amount_to_burn = lp_amount
+        .checked_sub(lp_fee)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+    // Use previous value of LP in order to avoid depositors, discounting any unrecognized loss.
     // Also add a 1 lamport fee due precision errors when withdrawing from lending protocols
-    let amount = previous_price.lp_to_token(lp_amount)?;
+    let amount = ctx
+        .accounts
+        .vault_account
+        .withdraw_lp_price()?
+        .lp_to_token(lp_amount_to_burn)?;
     let amount_conservative = amount.saturating_sub(1);
 
     require!(amount_conservative > 1, ErrorCode::InvalidZeroWithdraw);
+    require!(
+        amount_conservative >= min_tokens_out,
+        ErrorCode::SlippageExceeded
+    );
+
+    let available_amount = ctx
+        .accounts
+        .vault_account
+        .available_idle(ctx.accounts.vault_input_token_account.amount);
+    require!(
+        amount_conservative <= available_amount,
+        ErrorCode::InsufficientLiquidity
+    );
 
     let seeds = generate_seeds!(ctx.accounts.vault_account);
     let signer = &[&seeds[..]];
 
-    token::burn(ctx.accounts.burn_user_lps_ctx(), lp_amount)?;
+    if lp_fee > 0 {
+        token::transfer(
+            ctx.accounts.transfer_lps_from_user_to_treasury_ctx(),
+            lp_fee,
+        )?;
+    }
+    token::burn(ctx.accounts.burn_user_lps_ctx(), lp_amount_to_burn)?;
     token::transfer(
         ctx.accounts
             .transfer_from_vault_to_user_ctx()
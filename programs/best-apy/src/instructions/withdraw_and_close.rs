@@ -1,6 +1,7 @@
+use crate::config::ConfigAccount;
 use crate::macros::generate_seeds;
 use crate::vault::VaultAccount;
-use crate::VAULT_ACCOUNT_SEED;
+use crate::{CONFIG_ACCOUNT_SEED, VAULT_ACCOUNT_SEED};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use anchor_spl::token::{self, Token, TokenAccount, Transfer};
@@ -8,6 +9,11 @@ use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 #[derive(Accounts)]
 pub struct WithdrawAndClose<'info> {
     pub user_signer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_ACCOUNT_SEED],
+        bump = config_account.bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
     #[account(
         mut,
         associated_token::mint = vault_account.input_mint_pubkey,
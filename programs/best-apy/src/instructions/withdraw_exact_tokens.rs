@@ -0,0 +1,73 @@
+use crate::error::ErrorCode;
+use crate::instructions::Withdraw;
+use crate::macros::generate_seeds;
+use anchor_lang::prelude::*;
+use anchor_spl::token;
+
+/// Withdraw exactly `amount` input tokens from the vault and send them to the user, burning the
+/// required LPs (rounded up) and reverting if they exceed `max_lp_in`
+pub fn handler(ctx: Context<Withdraw>, amount: u64, max_lp_in: u64) -> Result<()> {
+    let current_price = ctx.accounts.current_lp_price();
+    let previous_price = ctx.accounts.vault_account.previous_lp_price;
+
+    if previous_price.minted_tokens != 0 {
+        require!(current_price >= previous_price, ErrorCode::InvalidLpPrice);
+    }
+
+    require!(amount > 1, ErrorCode::InvalidZeroWithdraw);
+
+    // Use previous value of LP in order to avoid depositors, discounting any unrecognized loss.
+    // Also add a 1 lamport fee due precision errors when withdrawing from lending protocols
+    let amount_with_precision_fee = amount
+        .checked_add(1)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+    let lp_amount_to_burn = ctx
+        .accounts
+        .vault_account
+        .withdraw_lp_price()?
+        .token_to_lp_round_up(amount_with_precision_fee)?;
+
+    let lp_fee = ctx
+        .accounts
+        .vault_account
+        .fees
+        .withdraw_fee_lp_on_burned(lp_amount_to_burn)?;
+    let lp_amount = lp_amount_to_burn
+        .checked_add(lp_fee)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+    require!(lp_amount <= max_lp_in, ErrorCode::SlippageExceeded);
+
+    let available_amount = ctx
+        .accounts
+        .vault_account
+        .available_idle(ctx.accounts.vault_input_token_account.amount);
+    require!(amount <= available_amount, ErrorCode::InsufficientLiquidity);
+
+    let seeds = generate_seeds!(ctx.accounts.vault_account);
+    let signer = &[&seeds[..]];
+
+    if lp_fee > 0 {
+        token::transfer(
+            ctx.accounts.transfer_lps_from_user_to_treasury_ctx(),
+            lp_fee,
+        )?;
+    }
+    token::burn(ctx.accounts.burn_user_lps_ctx(), lp_amount_to_burn)?;
+    token::transfer(
+        ctx.accounts
+            .transfer_from_vault_to_user_ctx()
+            .with_signer(signer),
+        amount,
+    )?;
+
+    // Update total withdraw (assuming we have lost 1 lamport due precision errors)
+    ctx.accounts.vault_account.current_tvl = ctx
+        .accounts
+        .vault_account
+        .current_tvl
+        .checked_sub(amount_with_precision_fee)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+    Ok(())
+}
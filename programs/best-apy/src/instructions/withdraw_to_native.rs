@@ -0,0 +1,103 @@
+use crate::instructions::{self, Withdraw};
+use crate::vault::VaultAccount;
+use crate::{USER_WRAPPED_ACCOUNT_SEED, VAULT_ACCOUNT_SEED, VAULT_LP_TOKEN_MINT_SEED};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program_option::COption, pubkey::Pubkey};
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount};
+
+#[derive(Accounts)]
+pub struct WithdrawToNative<'info> {
+    #[account(mut)]
+    pub user_signer: Signer<'info>,
+    #[account(
+        init,
+        payer = user_signer,
+        seeds = [USER_WRAPPED_ACCOUNT_SEED, vault_account.key().as_ref(), user_signer.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = user_signer,
+    )]
+    pub user_wrapped_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = user_lp_token_account.owner == *user_signer.key)]
+    pub user_lp_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = vault_account.input_mint_pubkey == spl_token::native_mint::ID,
+        seeds = [VAULT_ACCOUNT_SEED, &[vault_account.seed_number][..], vault_account.input_mint_pubkey.as_ref()],
+        bump = vault_account.bumps.vault
+    )]
+    pub vault_account: Box<Account<'info, VaultAccount>>,
+    #[account(
+        mut,
+        constraint = vault_lp_token_mint_pubkey.mint_authority == COption::Some(vault_account.key()),
+        seeds = [VAULT_LP_TOKEN_MINT_SEED, vault_account.key().as_ref()],
+        bump = vault_account.bumps.lp_token_mint
+    )]
+    pub vault_lp_token_mint_pubkey: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = vault_account.input_mint_pubkey,
+        associated_token::authority = vault_account,
+    )]
+    pub vault_input_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = vault_account.dao_treasury_lp_token_account)]
+    pub dao_treasury_lp_token_account: Account<'info, TokenAccount>,
+    #[account(address = spl_token::native_mint::ID)]
+    pub native_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> WithdrawToNative<'info> {
+    fn close_wrapped_account_ctx(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            CloseAccount {
+                account: self.user_wrapped_account.to_account_info(),
+                destination: self.user_signer.to_account_info(),
+                authority: self.user_signer.to_account_info(),
+            },
+        )
+    }
+
+    fn to_withdraw_accounts(&self) -> Withdraw<'info> {
+        Withdraw {
+            user_signer: self.user_signer.clone(),
+            user_input_token_account: self.user_wrapped_account.clone(),
+            user_lp_token_account: self.user_lp_token_account.clone(),
+            vault_account: self.vault_account.clone(),
+            vault_lp_token_mint_pubkey: self.vault_lp_token_mint_pubkey.clone(),
+            vault_input_token_account: self.vault_input_token_account.clone(),
+            dao_treasury_lp_token_account: self.dao_treasury_lp_token_account.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+}
+
+/// Withdraw from the vault into a temporary wrapped SOL account, which is closed afterwards
+/// sending the native SOL back to the user
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawToNative<'info>>,
+    lp_amount: u64,
+    min_tokens_out: u64,
+) -> Result<()> {
+    let mut withdraw_accounts = ctx.accounts.to_withdraw_accounts();
+    instructions::withdraw::handler(
+        Context::new(
+            ctx.program_id,
+            &mut withdraw_accounts,
+            ctx.remaining_accounts,
+            ctx.bumps,
+        ),
+        lp_amount,
+        min_tokens_out,
+    )?;
+
+    // Keep the vault state updated by the withdrawal
+    ctx.accounts.vault_account = withdraw_accounts.vault_account;
+
+    token::close_account(ctx.accounts.close_wrapped_account_ctx())?;
+
+    Ok(())
+}
@@ -2,6 +2,7 @@ use anchor_lang::prelude::borsh::{BorshDeserialize, BorshSerialize};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use check_hash::{CheckHash, CHECKHASH_BYTES};
+use config::{ConfigAccount, Role};
 use error::ErrorCode;
 use instructions::*;
 use protocols::{
@@ -12,67 +13,138 @@ use protocols::{
     tulip::*,
     Protocols,
 };
-use vault::{RefreshParams, VaultAccount};
+use vault::{DampingParams, FeeParams, PauseFlags, RefreshParams, VaultAccount, WeightStrategy};
 
 mod check_hash;
+mod config;
 mod error;
 mod instructions;
 mod macros;
 mod protocols;
+mod registry;
+mod ticket;
 mod vault;
+mod vault_index;
 
 declare_id!("GGo1dnYpjKfe9omzUaFtaCyizvwpAMf3NhxSCMD61F3A");
 
-const PAUSED_DEPOSIT: bool = true;
-const PAUSED_WITHDRAW: bool = true;
+const CONFIG_ACCOUNT_SEED: &[u8; 6] = b"config";
 
 const VAULT_ACCOUNT_SEED: &[u8; 5] = b"vault";
 const VAULT_LP_TOKEN_MINT_SEED: &[u8; 4] = b"mint";
 
 const VAULT_TICKET_MINT_SEED: &[u8; 11] = b"ticket_mint";
 
+const VAULT_INDEX_SEED: &[u8; 11] = b"vault_index";
+const REGISTRY_SEED: &[u8; 8] = b"registry";
+
+const USER_WRAPPED_ACCOUNT_SEED: &[u8; 7] = b"wrapped";
+
+const WITHDRAW_EPOCH_SEED: &[u8; 14] = b"withdraw_epoch";
+const WITHDRAW_TICKET_SEED: &[u8; 15] = b"withdraw_ticket";
+
+// Authority allowed to initialize the config account
 // DrrB1p8sxhwBZ3cXE8u5t2GxqEcTNuwAm7RcrQ8Yqjod
 const ADMIN_PUBKEY: Pubkey = Pubkey::new_from_array([
     191, 17, 77, 109, 253, 243, 16, 188, 64, 67, 249, 18, 51, 62, 173, 81, 128, 208, 121, 29, 74,
     57, 94, 247, 114, 4, 114, 88, 209, 115, 147, 136,
 ]);
 
-// 8XhNoDjjNoLP5Rys1pBJKGdE8acEC1HJsWGkfkMt6JP1
-const TREASURY_PUBKEY: Pubkey = Pubkey::new_from_array([
-    111, 222, 226, 197, 174, 64, 51, 181, 235, 205, 56, 138, 76, 105, 173, 158, 191, 43, 143, 141,
-    91, 145, 78, 45, 130, 86, 102, 175, 146, 188, 82, 152,
-]);
-
 #[program]
 pub mod best_apy {
     use super::*;
 
+    /// Initialize the config account
+    #[access_control(is_deployer(ctx.accounts.user_signer.key))]
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        instructions::initialize_config::handler(ctx)
+    }
+
+    /// Propose a new admin
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::propose_admin::handler(ctx, new_admin)
+    }
+
+    /// Accept the admin authority transfer
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        instructions::accept_admin::handler(ctx)
+    }
+
+    /// Set the guardian and strategist authorities
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_roles(ctx: Context<SetRoles>, guardian: Pubkey, strategist: Pubkey) -> Result<()> {
+        instructions::set_roles::handler(ctx, guardian, strategist)
+    }
+
     /// Transfer funds to admin to simplify user reimbursements
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn withdraw_and_close(ctx: Context<WithdrawAndClose>) -> Result<()> {
         instructions::withdraw_and_close::handler(ctx)
     }
 
     /// Initialize the vault account and its fields
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn initialize_vault(ctx: Context<InitializeVault>, account_number: u8) -> Result<()> {
         instructions::initialize_vault::handler(ctx, account_number)
     }
 
     /// Initialize the ticket mint
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn initialize_ticket_mint(ctx: Context<InitializeTicketMint>) -> Result<()> {
         instructions::initialize_ticket_mint::handler(ctx)
     }
 
     /// Add a new protocol to the vault_account
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn add_protocol(ctx: Context<AddProtocol>, protocol_id: u8) -> Result<()> {
         instructions::add_protocol::handler(ctx, protocol_id)
     }
 
+    /// Set the allocation caps of a protocol
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_protocol_caps(
+        ctx: Context<SetProtocolCaps>,
+        protocol_id: u8,
+        max_weight: u32,
+        max_amount: u64,
+    ) -> Result<()> {
+        instructions::set_protocol_caps::handler(ctx, protocol_id, max_weight, max_amount)
+    }
+
+    /// Set the risk score of a protocol
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_protocol_risk_score(
+        ctx: Context<SetProtocolRiskScore>,
+        protocol_id: u8,
+        risk_score: u8,
+    ) -> Result<()> {
+        instructions::set_protocol_risk_score::handler(ctx, protocol_id, risk_score)
+    }
+
+    /// Set the maximum share of a protocol reserve owned by the vault
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_max_reserve_share(
+        ctx: Context<SetMaxReserveShare>,
+        max_reserve_share_bps: u16,
+    ) -> Result<()> {
+        instructions::set_max_reserve_share::handler(ctx, max_reserve_share_bps)
+    }
+
+    /// Register a vault created before the registry
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn register_vault(ctx: Context<RegisterVault>) -> Result<()> {
+        instructions::register_vault::handler(ctx)
+    }
+
+    /// Delist the vault from the registry
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn delist_vault(ctx: Context<DelistVault>) -> Result<()> {
+        instructions::delist_vault::handler(ctx)
+    }
+
     /// Set protocol hashes
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn set_hashes(
         ctx: Context<SetHashes>,
         protocol_id: u8,
@@ -81,41 +153,108 @@ pub mod best_apy {
         instructions::set_hashes::handler(ctx, protocol_id, hashes)
     }
 
+    /// Set the vault fees
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_fees(ctx: Context<SetFees>, fees: FeeParams) -> Result<()> {
+        instructions::set_fees::handler(ctx, fees)
+    }
+
+    /// Set the bounty paid to the keepers settling withdrawal tickets
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_settle_bounty(ctx: Context<SetSettleBounty>, bounty_bps: u16) -> Result<()> {
+        instructions::set_settle_bounty::handler(ctx, bounty_bps)
+    }
+
+    /// Set the treasury account receiving the vault fees
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_treasury(ctx: Context<SetTreasury>) -> Result<()> {
+        instructions::set_treasury::handler(ctx)
+    }
+
     /// Set the strategy refresh paraemeters
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_strategist(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn set_refresh_params(ctx: Context<SetRefreshParams>, params: RefreshParams) -> Result<()> {
         instructions::set_refresh_params::handler(ctx, params)
     }
 
+    /// Set the rebalance damping parameters
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_damping_params(ctx: Context<SetDampingParams>, params: DampingParams) -> Result<()> {
+        instructions::set_damping_params::handler(ctx, params)
+    }
+
+    /// Set the operations paused in the vault
+    #[access_control(is_guardian(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_pause_flags(ctx: Context<SetPauseFlags>, flags: PauseFlags) -> Result<()> {
+        instructions::set_pause_flags::handler(ctx, flags)
+    }
+
     /// Set the protocol weights
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_strategist(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn set_protocol_weights(ctx: Context<SetProtocolWeights>, weights: Vec<u32>) -> Result<()> {
         instructions::set_protocol_weights::handler(ctx, weights)
     }
 
+    /// Set the algorithm used to update the protocol weights
+    #[access_control(is_strategist(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_weight_strategy(
+        ctx: Context<SetWeightStrategy>,
+        weight_strategy: WeightStrategy,
+    ) -> Result<()> {
+        instructions::set_weight_strategy::handler(ctx, weight_strategy)
+    }
+
+    /// Set the percentage of the TVL kept idle in the vault
+    #[access_control(is_strategist(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn set_idle_weight(ctx: Context<SetIdleWeight>, idle_weight: u32) -> Result<()> {
+        instructions::set_idle_weight::handler(ctx, idle_weight)
+    }
+
     /// Deposit user input tokens into the vault account
-    #[access_control(deposit_not_paused())]
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        instructions::deposit::handler(ctx, amount)
+    #[access_control(deposit_not_paused(&ctx.accounts.vault_account))]
+    pub fn deposit(ctx: Context<Deposit>, amount: u64, min_lp_out: u64) -> Result<()> {
+        instructions::deposit::handler(ctx, amount, min_lp_out)
     }
 
     /// Deposit user input native SOL into the vault account
-    #[access_control(deposit_not_paused())]
+    #[access_control(deposit_not_paused(&ctx.accounts.vault_account))]
     pub fn deposit_from_native<'info>(
         ctx: Context<'_, '_, '_, 'info, DepositFromNative<'info>>,
         amount: u64,
+        min_lp_out: u64,
     ) -> Result<()> {
-        instructions::deposit_from_native::handler(ctx, amount)
+        instructions::deposit_from_native::handler(ctx, amount, min_lp_out)
     }
 
     /// Withdraw the required input tokens from the vault and send them back to the user
-    #[access_control(withdraw_not_paused())]
-    pub fn withdraw(ctx: Context<Withdraw>, lp_amount: u64) -> Result<()> {
-        instructions::withdraw::handler(ctx, lp_amount)
+    #[access_control(withdraw_not_paused(&ctx.accounts.vault_account))]
+    pub fn withdraw(ctx: Context<Withdraw>, lp_amount: u64, min_tokens_out: u64) -> Result<()> {
+        instructions::withdraw::handler(ctx, lp_amount, min_tokens_out)
+    }
+
+    /// Withdraw the exact amount of input tokens from the vault and send them back to the user
+    #[access_control(withdraw_not_paused(&ctx.accounts.vault_account))]
+    pub fn withdraw_exact_tokens(
+        ctx: Context<Withdraw>,
+        amount: u64,
+        max_lp_in: u64,
+    ) -> Result<()> {
+        instructions::withdraw_exact_tokens::handler(ctx, amount, max_lp_in)
+    }
+
+    /// Withdraw the required input tokens from a wrapped SOL vault and send them back to the
+    /// user as native SOL
+    #[access_control(withdraw_not_paused(&ctx.accounts.vault_account))]
+    pub fn withdraw_to_native<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawToNative<'info>>,
+        lp_amount: u64,
+        min_tokens_out: u64,
+    ) -> Result<()> {
+        instructions::withdraw_to_native::handler(ctx, lp_amount, min_tokens_out)
     }
 
     // Mango reimbursement
-    #[access_control(is_admin(ctx.accounts.user_signer.key))]
+    #[access_control(is_admin(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
     pub fn mango_reimbursement(
         ctx: Context<MangoReimbursement>,
         token_index: u8,
@@ -132,7 +271,7 @@ pub mod best_apy {
     }
 
     /// Open a withdrawal ticket (for delayed withdrawals)
-    #[access_control(withdraw_not_paused())]
+    #[access_control(open_ticket_not_paused(&ctx.accounts.vault_account))]
     pub fn open_withdraw_ticket(
         ctx: Context<OpenWithdrawTicket>,
         bump_user: u8,
@@ -141,14 +280,72 @@ pub mod best_apy {
         instructions::open_withdraw_ticket::handler(ctx, bump_user, lp_amount)
     }
 
+    /// Queue the LPs locked by a legacy withdrawal ticket in the current epoch
+    #[access_control(open_ticket_not_paused(&ctx.accounts.vault_account))]
+    pub fn migrate_withdraw_ticket(
+        ctx: Context<MigrateWithdrawTicket>,
+        bump_user: u8,
+    ) -> Result<()> {
+        instructions::migrate_withdraw_ticket::handler(ctx, bump_user)
+    }
+
+    /// Cancel a withdrawal ticket, recovering the locked LPs
+    pub fn cancel_withdraw_ticket(
+        ctx: Context<CancelWithdrawTicket>,
+        bump_user: u8,
+        lp_amount: u64,
+    ) -> Result<()> {
+        instructions::cancel_withdraw_ticket::handler(ctx, bump_user, lp_amount)
+    }
+
+    /// Fill the oldest pending withdrawal epoch with the idle liquidity, closing it to new tickets
+    #[access_control(
+        withdraw_not_paused(&ctx.accounts.vault_account)
+        is_strategist(&ctx.accounts.config_account, ctx.accounts.user_signer.key)
+    )]
+    pub fn fill_withdraw_epoch(ctx: Context<FillWithdrawEpoch>, amount: u64) -> Result<()> {
+        instructions::fill_withdraw_epoch::handler(ctx, amount)
+    }
+
     /// Close a withdrawal ticket
-    #[access_control(withdraw_not_paused())]
+    #[access_control(withdraw_not_paused(&ctx.accounts.vault_account))]
     pub fn close_withdraw_ticket(
         ctx: Context<CloseWithdrawTicket>,
         bump_user: u8,
-        lp_amount: u64,
+        min_tokens_out: u64,
+    ) -> Result<()> {
+        instructions::close_withdraw_ticket::handler(ctx, bump_user, min_tokens_out)
+    }
+
+    /// Close a withdrawal ticket of a wrapped SOL vault, sending native SOL back to the user
+    #[access_control(withdraw_not_paused(&ctx.accounts.vault_account))]
+    pub fn close_withdraw_ticket_to_native<'info>(
+        ctx: Context<'_, '_, '_, 'info, CloseWithdrawTicketToNative<'info>>,
+        bump_user: u8,
+        min_tokens_out: u64,
     ) -> Result<()> {
-        instructions::close_withdraw_ticket::handler(ctx, bump_user, lp_amount)
+        instructions::close_withdraw_ticket_to_native::handler(ctx, bump_user, min_tokens_out)
+    }
+
+    /// Settle a withdrawal ticket on behalf of its owner
+    #[access_control(withdraw_not_paused(&ctx.accounts.vault_account))]
+    pub fn settle_withdraw_ticket(ctx: Context<SettleWithdrawTicket>, bump_user: u8) -> Result<()> {
+        instructions::settle_withdraw_ticket::handler(ctx, bump_user)
+    }
+
+    /// Close the empty withdrawal ticket accounts of the user
+    pub fn close_withdraw_ticket_accounts(
+        ctx: Context<CloseWithdrawTicketAccounts>,
+        bump_user: u8,
+    ) -> Result<()> {
+        instructions::close_withdraw_ticket_accounts::handler(ctx, bump_user)
+    }
+
+    /// Compute the TVL of every protocol in the vault
+    pub fn refresh_all_tvl<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefreshAllTvl<'info>>,
+    ) -> Result<()> {
+        instructions::refresh_all_tvl::handler(ctx)
     }
 
     /// Refresh the protocol weights
@@ -156,6 +353,23 @@ pub mod best_apy {
         instructions::refresh_weights::handler(ctx)
     }
 
+    /// Withdraw from the over-weight protocols and deposit into the under-weight ones
+    #[access_control(rebalance_not_paused(&ctx.accounts.vault_account))]
+    pub fn rebalance<'info>(ctx: Context<'_, '_, '_, 'info, Rebalance<'info>>) -> Result<()> {
+        instructions::rebalance::handler(ctx)
+    }
+
+    /// Recognize a realized loss of the vault
+    #[access_control(is_guardian(&ctx.accounts.config_account, ctx.accounts.user_signer.key))]
+    pub fn recognize_loss(ctx: Context<RecognizeLoss>, loss: u64) -> Result<()> {
+        instructions::recognize_loss::handler(ctx, loss)
+    }
+
+    /// Reconcile the vault holdings with its TVL
+    pub fn sync_idle_balance(ctx: Context<SyncIdleBalance>) -> Result<()> {
+        instructions::sync_idle_balance::handler(ctx)
+    }
+
     ///// Mango: Initialize protocol accounts
     //#[access_control(is_admin(ctx.accounts.user_signer.key))]
     //pub fn mango_initialize(ctx: Context<MangoInitialize>) -> Result<()> {
@@ -181,7 +395,10 @@ pub mod best_apy {
     //}
 
     /// Solend: Deposit from the vault account
-    #[access_control(ctx.accounts.check_hash(Protocols::Solend))]
+    #[access_control(
+        ctx.accounts.check_hash(Protocols::Solend),
+        rebalance_not_paused(&ctx.accounts.generic_accs.vault_account)
+    )]
     pub fn solend_deposit(ctx: Context<SolendDeposit>) -> Result<()> {
         instructions::protocol_deposit::handler(ctx, Protocols::Solend)
     }
@@ -199,7 +416,10 @@ pub mod best_apy {
     }
 
     /// SolendIsolatedPool: Deposit from the vault account
-    #[access_control(ctx.accounts.check_hash(Protocols::SolendStablePool))]
+    #[access_control(
+        ctx.accounts.check_hash(Protocols::SolendStablePool),
+        rebalance_not_paused(&ctx.accounts.generic_accs.vault_account)
+    )]
     pub fn solend_isolated_pool_deposit(ctx: Context<SolendDeposit>) -> Result<()> {
         instructions::protocol_deposit::handler(ctx, Protocols::SolendStablePool)
     }
@@ -217,7 +437,10 @@ pub mod best_apy {
     }
 
     /// Port: Deposit from the vault account
-    #[access_control(ctx.accounts.check_hash(Protocols::Port))]
+    #[access_control(
+        ctx.accounts.check_hash(Protocols::Port),
+        rebalance_not_paused(&ctx.accounts.generic_accs.vault_account)
+    )]
     pub fn port_deposit(ctx: Context<PortDeposit>) -> Result<()> {
         instructions::protocol_deposit::handler(ctx, Protocols::Port)
     }
@@ -235,7 +458,10 @@ pub mod best_apy {
     }
 
     /// Tulip: Deposit from the vault account
-    #[access_control(ctx.accounts.check_hash(Protocols::Tulip))]
+    #[access_control(
+        ctx.accounts.check_hash(Protocols::Tulip),
+        rebalance_not_paused(&ctx.accounts.generic_accs.vault_account)
+    )]
     pub fn tulip_deposit(ctx: Context<TulipDeposit>) -> Result<()> {
         instructions::protocol_deposit::handler(ctx, Protocols::Tulip)
     }
@@ -253,7 +479,10 @@ pub mod best_apy {
     }
 
     /// Francium: Deposit from the vault account
-    #[access_control(ctx.accounts.check_hash(Protocols::Francium))]
+    #[access_control(
+        ctx.accounts.check_hash(Protocols::Francium),
+        rebalance_not_paused(&ctx.accounts.generic_accs.vault_account)
+    )]
     pub fn francium_deposit(ctx: Context<FranciumDeposit>) -> Result<()> {
         instructions::protocol_deposit::handler(ctx, Protocols::Francium)
     }
@@ -272,20 +501,50 @@ pub mod best_apy {
 }
 
 /// Check if the deposit is paused
-fn deposit_not_paused() -> Result<()> {
-    require!(!PAUSED_DEPOSIT, ErrorCode::OnPaused);
+fn deposit_not_paused(vault: &VaultAccount) -> Result<()> {
+    require!(!vault.pause_flags().deposit, ErrorCode::OnPaused);
     Ok(())
 }
 
 /// Check if the withdraw is paused
-fn withdraw_not_paused() -> Result<()> {
-    require!(!PAUSED_WITHDRAW, ErrorCode::OnPaused);
+fn withdraw_not_paused(vault: &VaultAccount) -> Result<()> {
+    require!(!vault.pause_flags().withdraw, ErrorCode::OnPaused);
+    Ok(())
+}
+
+/// Check if opening withdrawal tickets is paused
+fn open_ticket_not_paused(vault: &VaultAccount) -> Result<()> {
+    require!(!vault.pause_flags().open_ticket, ErrorCode::OnPaused);
     Ok(())
 }
 
-/// Check if target key is authorized
-fn is_admin(key: &Pubkey) -> Result<()> {
+/// Check if moving funds from/to the protocols is paused
+fn rebalance_not_paused(vault: &VaultAccount) -> Result<()> {
+    require!(!vault.pause_flags().rebalance, ErrorCode::OnPaused);
+    Ok(())
+}
+
+/// Check if target key is allowed to initialize the config account
+fn is_deployer(key: &Pubkey) -> Result<()> {
     #[cfg(not(feature = "test"))]
     require!(key == &ADMIN_PUBKEY, UnauthorizedUser);
     Ok(())
 }
+
+/// Check if target key is the admin
+fn is_admin(config: &ConfigAccount, key: &Pubkey) -> Result<()> {
+    require!(config.has_role(key, Role::Admin), UnauthorizedUser);
+    Ok(())
+}
+
+/// Check if target key is the guardian (or the admin)
+fn is_guardian(config: &ConfigAccount, key: &Pubkey) -> Result<()> {
+    require!(config.has_role(key, Role::Guardian), UnauthorizedUser);
+    Ok(())
+}
+
+/// Check if target key is the strategist (or the admin)
+fn is_strategist(config: &ConfigAccount, key: &Pubkey) -> Result<()> {
+    require!(config.has_role(key, Role::Strategist), UnauthorizedUser);
+    Ok(())
+}
@@ -3,7 +3,7 @@ use crate::error::ErrorCode;
 use crate::instructions::{protocol_deposit::*, protocol_rewards::*, protocol_withdraw::*};
 use crate::macros::generate_seeds;
 use crate::protocols::{state::francium_lending_pool, Protocols};
-use crate::vault::ProtocolData;
+use crate::vault::{ProtocolData, ReserveRates};
 use anchor_lang::prelude::borsh::{BorshDeserialize, BorshSerialize};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
@@ -96,7 +96,15 @@ impl<'info> ProtocolDeposit<'info> for FranciumDeposit<'info> {
     }
 
     fn get_amount(&self, protocol_idx: usize) -> Result<u64> {
-        self.generic_accs.amount_to_deposit(protocol_idx)
+        self.generic_accs
+            .amount_to_deposit(protocol_idx, self.reserve_supply()?)
+    }
+
+    fn reserve_supply(&self) -> Result<u64> {
+        let lending_pool = francium_lending_pool::LendingPool::unpack(
+            &self.francium_lending_pool_info_account.data.borrow(),
+        )?;
+        Ok(lending_pool.liquidity.total_supply()?.try_floor_u64()?)
     }
 
     fn cpi_deposit(&self, amount: u64) -> Result<()> {
@@ -326,24 +334,74 @@ impl<'info> ProtocolRewards<'info> for FranciumTVL<'info> {
         &mut self.generic_accs.vault_account.protocols[protocol_idx]
     }
 
-    fn max_withdrawable(&self) -> Result<u64> {
-        let lending = francium_lending_pool::LendingPool::unpack(&self.lending_pool.data.borrow())?;
-
-        require!(
-            lending.liquidity.mint_pubkey == self.generic_accs.vault_account.input_mint_pubkey,
-            ErrorCode::InvalidMint
-        );
+    fn reserve_rates(&self) -> Result<ReserveRates> {
+        reserve_rates(&self.lending_pool)
+    }
 
-        require!(
-            lending.share.mint_pubkey == self.vault_francium_collateral_token_account.mint,
-            ErrorCode::InvalidMint
-        );
+    fn max_withdrawable(&self) -> Result<u64> {
+        tvl(
+            &self.lending_pool,
+            &self.vault_francium_collateral_token_account,
+            &self.generic_accs.vault_account.input_mint_pubkey,
+        )
+    }
+}
 
-        let lp_amount = self.vault_francium_collateral_token_account.amount;
-        let tvl = lending
-            .collateral_exchange_rate()?
-            .collateral_to_liquidity(lp_amount)?;
+/// Compute the TVL of the vault in the protocol from its collateral token account
+pub fn tvl(
+    lending_pool: &AccountInfo,
+    collateral_token_account: &TokenAccount,
+    input_mint_pubkey: &Pubkey,
+) -> Result<u64> {
+    require!(
+        lending_pool.owner == &francium_lending_program_id::ID,
+        ErrorCode::InvalidOwner
+    );
+
+    let lending = francium_lending_pool::LendingPool::unpack(&lending_pool.data.borrow())?;
+
+    require!(
+        lending.liquidity.mint_pubkey == *input_mint_pubkey,
+        ErrorCode::InvalidMint
+    );
+
+    require!(
+        lending.share.mint_pubkey == collateral_token_account.mint,
+        ErrorCode::InvalidMint
+    );
+
+    let lp_amount = collateral_token_account.amount;
+    let tvl = lending
+        .collateral_exchange_rate()?
+        .collateral_to_liquidity(lp_amount)?;
+
+    Ok(tvl)
+}
 
-        Ok(tvl)
-    }
+/// Snapshot of the lending pool supply and borrow rate curve. Each of the three segments of the
+/// curve starts at its base rate and grows by its factor over the whole utilization range (all in
+/// percent). The curve is kept continuous taking the end of each segment as the start of the next
+/// one
+pub fn reserve_rates(lending_pool: &AccountInfo) -> Result<ReserveRates> {
+    let lending = francium_lending_pool::LendingPool::unpack(&lending_pool.data.borrow())?;
+
+    let threshold_1 = std::cmp::min(lending.threshold_1, 100);
+    let threshold_2 = std::cmp::max(std::cmp::min(lending.threshold_2, 100), threshold_1);
+    let rate_bps = |base: u8, factor: u16, utilization: u8| -> u16 {
+        let rate = base as u64 * 100 + factor as u64 * utilization as u64;
+        std::cmp::min(rate, u16::MAX as u64) as u16
+    };
+
+    Ok(ReserveRates {
+        total_supply: lending.liquidity.total_supply()?.try_floor_u64()?,
+        borrowed_amount: lending.liquidity.borrowed_amount_wads.try_floor_u64()?,
+        curve_utilization: [threshold_1, threshold_2],
+        curve_borrow_rate_bps: [
+            rate_bps(lending.base_1, 0, 0),
+            rate_bps(lending.base_1, lending.factor_1, threshold_1),
+            rate_bps(lending.base_2, lending.factor_2, threshold_2 - threshold_1),
+            rate_bps(lending.base_3, lending.factor_3, 100 - threshold_2),
+        ],
+        take_rate: lending.interest_reverse_rate,
+    })
 }
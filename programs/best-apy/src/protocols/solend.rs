@@ -3,7 +3,7 @@ use crate::error::ErrorCode;
 use crate::instructions::{protocol_deposit::*, protocol_rewards::*, protocol_withdraw::*};
 use crate::macros::generate_seeds;
 use crate::protocols::Protocols;
-use crate::vault::ProtocolData;
+use crate::vault::{ProtocolData, ReserveRates};
 
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
@@ -86,7 +86,15 @@ impl<'info> ProtocolDeposit<'info> for SolendDeposit<'info> {
     }
 
     fn get_amount(&self, protocol_idx: usize) -> Result<u64> {
-        self.generic_accs.amount_to_deposit(protocol_idx)
+        self.generic_accs
+            .amount_to_deposit(protocol_idx, self.reserve_supply()?)
+    }
+
+    fn reserve_supply(&self) -> Result<u64> {
+        let reserve = solend_token_lending::state::Reserve::unpack(
+            &self.solend_reserve_account.data.borrow(),
+        )?;
+        Ok(reserve.liquidity.total_supply()?.try_floor_u64()?)
     }
 
     fn cpi_deposit(&self, amount: u64) -> Result<()> {
@@ -298,24 +306,60 @@ impl<'info> ProtocolRewards<'info> for SolendTVL<'info> {
         &mut self.generic_accs.vault_account.protocols[protocol_idx]
     }
 
-    fn max_withdrawable(&self) -> Result<u64> {
-        let reserve = solend_token_lending::state::Reserve::unpack(&self.reserve.data.borrow())?;
-
-        require!(
-            reserve.liquidity.mint_pubkey == self.generic_accs.vault_account.input_mint_pubkey,
-            ErrorCode::InvalidMint
-        );
+    fn reserve_rates(&self) -> Result<ReserveRates> {
+        reserve_rates(&self.reserve)
+    }
 
-        require!(
-            reserve.collateral.mint_pubkey == self.vault_solend_collateral_token_account.mint,
-            ErrorCode::InvalidMint
-        );
+    fn max_withdrawable(&self) -> Result<u64> {
+        tvl(
+            &self.reserve,
+            &self.vault_solend_collateral_token_account,
+            &self.generic_accs.vault_account.input_mint_pubkey,
+        )
+    }
+}
 
-        let lp_amount = self.vault_solend_collateral_token_account.amount;
-        let tvl = reserve
-            .collateral_exchange_rate()?
-            .collateral_to_liquidity(lp_amount)?;
+/// Compute the TVL of the vault in the protocol from its collateral token account
+pub fn tvl(
+    reserve: &AccountInfo,
+    collateral_token_account: &TokenAccount,
+    input_mint_pubkey: &Pubkey,
+) -> Result<u64> {
+    require!(
+        reserve.owner == &solend_program_id::ID,
+        ErrorCode::InvalidOwner
+    );
+
+    let reserve = solend_token_lending::state::Reserve::unpack(&reserve.data.borrow())?;
+
+    require!(
+        reserve.liquidity.mint_pubkey == *input_mint_pubkey,
+        ErrorCode::InvalidMint
+    );
+
+    require!(
+        reserve.collateral.mint_pubkey == collateral_token_account.mint,
+        ErrorCode::InvalidMint
+    );
+
+    let lp_amount = collateral_token_account.amount;
+    let tvl = reserve
+        .collateral_exchange_rate()?
+        .collateral_to_liquidity(lp_amount)?;
+
+    Ok(tvl)
+}
 
-        Ok(tvl)
-    }
+/// Snapshot of the reserve supply and borrow rate curve
+pub fn reserve_rates(reserve: &AccountInfo) -> Result<ReserveRates> {
+    let reserve = solend_token_lending::state::Reserve::unpack(&reserve.data.borrow())?;
+
+    Ok(ReserveRates::from_two_slopes_curve(
+        reserve.liquidity.total_supply()?.try_floor_u64()?,
+        reserve.liquidity.borrowed_amount_wads.try_floor_u64()?,
+        reserve.config.optimal_utilization_rate,
+        reserve.config.min_borrow_rate,
+        reserve.config.optimal_borrow_rate,
+        reserve.config.max_borrow_rate,
+    ))
 }
@@ -28,6 +28,8 @@ pub struct Reserve {
     pub liquidity: ReserveLiquidity,
     /// Reserve collateral
     pub collateral: ReserveCollateral,
+    /// Reserve configuration values
+    pub config: ReserveConfig,
 
     // Field from tulip reserve layout
     pub borrow_authorizer: Pubkey,
@@ -107,6 +109,19 @@ impl ReserveCollateral {
     }
 }
 
+/// Reserve configuration values (interest rate curve only)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReserveConfig {
+    /// Optimal utilization rate, as a percentage
+    pub optimal_utilization_rate: u8,
+    /// Min borrow APY
+    pub min_borrow_rate: u8,
+    /// Optimal (utilization) borrow APY
+    pub optimal_borrow_rate: u8,
+    /// Max borrow APY
+    pub max_borrow_rate: u8,
+}
+
 /// Collateral exchange rate
 #[derive(Clone, Copy, Debug)]
 pub struct CollateralExchangeRate(Rate);
@@ -177,7 +192,14 @@ impl Pack for Reserve {
             collateral_mint_pubkey,                // 32
             collateral_mint_total_supply,          // 8
             collateral_supply_pubkey,              // 32
-            _padding_and_other_fields,             // 274
+            config_optimal_utilization_rate,       // 1
+            _config_loan_to_value_ratio,           // 1
+            _config_liquidation_bonus,             // 1
+            _config_liquidation_threshold,         // 1
+            config_min_borrow_rate,                // 1
+            config_optimal_borrow_rate,            // 1
+            config_max_borrow_rate,                // 1
+            _padding_and_other_fields,             // 267
         ) = array_refs![
             input,
             1,            // 1
@@ -199,7 +221,14 @@ impl Pack for Reserve {
             PUBKEY_BYTES, // 32
             8,            // 8
             PUBKEY_BYTES, // 32
-            274           // 274
+            1,            // 1
+            1,            // 1
+            1,            // 1
+            1,            // 1
+            1,            // 1
+            1,            // 1
+            1,            // 1
+            267           // 267
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -234,6 +263,12 @@ impl Pack for Reserve {
                 mint_total_supply: u64::from_le_bytes(*collateral_mint_total_supply),
                 supply_pubkey: Pubkey::new_from_array(*collateral_supply_pubkey),
             },
+            config: ReserveConfig {
+                optimal_utilization_rate: u8::from_le_bytes(*config_optimal_utilization_rate),
+                min_borrow_rate: u8::from_le_bytes(*config_min_borrow_rate),
+                optimal_borrow_rate: u8::from_le_bytes(*config_optimal_borrow_rate),
+                max_borrow_rate: u8::from_le_bytes(*config_max_borrow_rate),
+            },
         })
     }
 }
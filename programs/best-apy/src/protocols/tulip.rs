@@ -4,7 +4,7 @@ use crate::instructions::{protocol_deposit::*, protocol_rewards::*, protocol_wit
 
 use crate::macros::generate_seeds;
 use crate::protocols::{state::tulip_reserve, Protocols};
-use crate::vault::ProtocolData;
+use crate::vault::{ProtocolData, ReserveRates};
 use anchor_lang::prelude::borsh::{BorshDeserialize, BorshSerialize};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
@@ -98,7 +98,13 @@ impl<'info> ProtocolDeposit<'info> for TulipDeposit<'info> {
     }
 
     fn get_amount(&self, protocol_idx: usize) -> Result<u64> {
-        self.generic_accs.amount_to_deposit(protocol_idx)
+        self.generic_accs
+            .amount_to_deposit(protocol_idx, self.reserve_supply()?)
+    }
+
+    fn reserve_supply(&self) -> Result<u64> {
+        let reserve = tulip_reserve::Reserve::unpack(&self.tulip_reserve_account.data.borrow())?;
+        Ok(reserve.liquidity.total_supply()?.try_floor_u64()?)
     }
 
     fn cpi_deposit(&self, amount: u64) -> Result<()> {
@@ -342,24 +348,64 @@ impl<'info> ProtocolRewards<'info> for TulipTVL<'info> {
         &mut self.generic_accs.vault_account.protocols[protocol_idx]
     }
 
-    fn max_withdrawable(&self) -> Result<u64> {
-        let reserve = tulip_reserve::Reserve::unpack(&self.reserve.data.borrow())?;
-
-        require!(
-            reserve.liquidity.mint_pubkey == self.generic_accs.vault_account.input_mint_pubkey,
-            ErrorCode::InvalidMint
-        );
+    fn reserve_rates(&self) -> Result<ReserveRates> {
+        reserve_rates(&self.reserve)
+    }
 
-        require!(
-            reserve.collateral.mint_pubkey == self.vault_tulip_collateral_token_account.mint,
-            ErrorCode::InvalidMint
-        );
+    fn max_withdrawable(&self) -> Result<u64> {
+        tvl(
+            &self.reserve,
+            &self.vault_tulip_collateral_token_account,
+            &self.generic_accs.vault_account.input_mint_pubkey,
+        )
+    }
+}
 
-        let lp_amount = self.vault_tulip_collateral_token_account.amount;
-        let tvl = reserve
-            .collateral_exchange_rate()?
-            .collateral_to_liquidity(lp_amount)?;
+/// Compute the TVL of the vault in the protocol from its collateral token account
+pub fn tvl(
+    reserve: &AccountInfo,
+    collateral_token_account: &TokenAccount,
+    input_mint_pubkey: &Pubkey,
+) -> Result<u64> {
+    require!(
+        reserve.owner == &tulip_program_id::ID,
+        ErrorCode::InvalidOwner
+    );
+
+    let reserve = tulip_reserve::Reserve::unpack(&reserve.data.borrow())?;
+
+    require!(
+        reserve.liquidity.mint_pubkey == *input_mint_pubkey,
+        ErrorCode::InvalidMint
+    );
+
+    require!(
+        reserve.collateral.mint_pubkey == collateral_token_account.mint,
+        ErrorCode::InvalidMint
+    );
+
+    let lp_amount = collateral_token_account.amount;
+    let tvl = reserve
+        .collateral_exchange_rate()?
+        .collateral_to_liquidity(lp_amount)?;
+
+    Ok(tvl)
+}
 
-        Ok(tvl)
-    }
+/// Snapshot of the reserve supply and borrow rate curve. The platform fees are taken from the
+/// borrow interests
+pub fn reserve_rates(reserve: &AccountInfo) -> Result<ReserveRates> {
+    let reserve = tulip_reserve::Reserve::unpack(&reserve.data.borrow())?;
+
+    Ok(ReserveRates {
+        take_rate: reserve.liquidity.platform_fees,
+        ..ReserveRates::from_two_slopes_curve(
+            reserve.liquidity.total_supply()?.try_floor_u64()?,
+            reserve.liquidity.borrowed_amount_wads.try_floor_u64()?,
+            reserve.config.optimal_utilization_rate,
+            reserve.config.min_borrow_rate,
+            reserve.config.optimal_borrow_rate,
+            reserve.config.max_borrow_rate,
+        )
+    })
 }
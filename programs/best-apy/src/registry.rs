@@ -0,0 +1,149 @@
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+
+/// Maximum number of vaults listed in the registry
+pub const MAX_REGISTRY_VAULTS: usize = 64;
+
+/// Registry listing every vault of the program
+#[account]
+#[derive(Default)]
+pub struct RegistryAccount {
+    /// PDA bump seed
+    pub bump: u8,
+    /// Registered vaults (maximum = 64)
+    pub vaults: Vec<RegistryEntry>,
+}
+
+impl RegistryAccount {
+    pub const SIZE: usize = 1 + 4 + RegistryEntry::SIZE * MAX_REGISTRY_VAULTS;
+
+    /// Append a new vault to the registry
+    pub fn register(&mut self, entry: RegistryEntry) -> Result<()> {
+        require!(
+            self.position(&entry.vault).is_none(),
+            ErrorCode::VaultAlreadyRegistered
+        );
+        require!(
+            self.vaults.len() < MAX_REGISTRY_VAULTS,
+            ErrorCode::RegistryFull
+        );
+        self.vaults.push(entry);
+        Ok(())
+    }
+
+    /// Find the registry entry of the vault
+    pub fn entry_mut(&mut self, vault: &Pubkey) -> Result<&mut RegistryEntry> {
+        self.get_mut(vault)
+            .ok_or_else(|| error!(ErrorCode::VaultNotRegistered))
+    }
+
+    /// Find the registry entry of the vault, if it was registered
+    pub fn get_mut(&mut self, vault: &Pubkey) -> Option<&mut RegistryEntry> {
+        let position = self.position(vault)?;
+        Some(&mut self.vaults[position])
+    }
+
+    fn position(&self, vault: &Pubkey) -> Option<usize> {
+        self.vaults.iter().position(|entry| &entry.vault == vault)
+    }
+}
+
+/// Registry data of a vault
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct RegistryEntry {
+    /// Vault account address
+    pub vault: Pubkey,
+    /// Vault input token mint address
+    pub input_mint_pubkey: Pubkey,
+    /// Vault seed number
+    pub seed_number: u8,
+    /// Vault LP token mint address
+    pub lp_token_mint_pubkey: Pubkey,
+    /// Vault listing status
+    pub status: VaultStatus,
+    /// Bitmap of the protocol ids added to the vault
+    pub protocols: u16,
+}
+
+impl RegistryEntry {
+    pub const SIZE: usize = 32 + 32 + 1 + 32 + 1 + 2;
+
+    /// Record the protocol as added to the vault
+    pub fn add_protocol(&mut self, protocol_id: u8) -> Result<()> {
+        let bit = 1u16
+            .checked_shl(protocol_id.into())
+            .ok_or_else(|| error!(ErrorCode::InvalidProtocolId))?;
+        self.protocols |= bit;
+        Ok(())
+    }
+
+    /// Check if the protocol was added to the vault
+    pub fn has_protocol(&self, protocol_id: u8) -> bool {
+        matches!(
+            1u16.checked_shl(protocol_id.into()),
+            Some(bit) if self.protocols & bit != 0
+        )
+    }
+}
+
+/// Vault listing status
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VaultStatus {
+    Active,
+    Delisted,
+}
+
+impl Default for VaultStatus {
+    fn default() -> Self {
+        VaultStatus::Active
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(vault: Pubkey) -> RegistryEntry {
+        RegistryEntry {
+            vault,
+            ..RegistryEntry::default()
+        }
+    }
+
+    #[test]
+    fn test_registry_register() {
+        let mut registry = RegistryAccount::default();
+        let vault = Pubkey::new_unique();
+
+        registry.register(entry(vault)).unwrap();
+        assert!(registry.register(entry(vault)).is_err());
+        assert!(registry.entry_mut(&Pubkey::new_unique()).is_err());
+        assert!(registry.get_mut(&Pubkey::new_unique()).is_none());
+
+        let registry_entry = registry.entry_mut(&vault).unwrap();
+        assert_eq!(registry_entry.status, VaultStatus::Active);
+        registry_entry.status = VaultStatus::Delisted;
+        assert_eq!(registry.vaults[0].status, VaultStatus::Delisted);
+
+        for _ in 1..MAX_REGISTRY_VAULTS {
+            registry.register(entry(Pubkey::new_unique())).unwrap();
+        }
+        assert!(registry.register(entry(Pubkey::new_unique())).is_err());
+    }
+
+    #[test]
+    fn test_registry_entry_protocols() {
+        let mut registry_entry = entry(Pubkey::new_unique());
+
+        registry_entry.add_protocol(1).unwrap();
+        registry_entry.add_protocol(5).unwrap();
+
+        assert!(registry_entry.has_protocol(1));
+        assert!(registry_entry.has_protocol(5));
+        assert!(!registry_entry.has_protocol(0));
+        assert!(!registry_entry.has_protocol(16));
+        assert_eq!(registry_entry.protocols, 0b100010);
+
+        assert!(registry_entry.add_protocol(16).is_err());
+    }
+}
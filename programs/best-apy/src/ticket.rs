@@ -0,0 +1,502 @@
+use crate::error::ErrorCode;
+use crate::vault::LpPrice;
+use anchor_lang::prelude::*;
+use std::convert::TryInto;
+
+/// Withdrawal epoch, grouping the tickets opened until the keeper starts filling it
+#[account]
+#[derive(Default)]
+pub struct WithdrawEpochAccount {
+    /// PDA bump seed
+    pub bump: u8,
+    /// Vault address
+    pub vault: Pubkey,
+    /// Epoch number
+    pub epoch: u64,
+    /// Timestamp of the first ticket opened in the epoch
+    pub opened_at: i64,
+    /// Timestamp of the first fill, after which no more tickets are accepted (zero if open)
+    pub closed_at: i64,
+    /// LPs locked by the tickets of the epoch
+    pub requested_lp: u64,
+    /// Input tokens requested by the tickets, valued at the LP price when opened
+    pub requested_amount: u64,
+    /// Input tokens to be paid, limited by the value of the locked LPs when closing the epoch
+    pub owed_amount: u64,
+    /// Input tokens set aside for the tickets so far
+    pub filled_amount: u64,
+    /// Locked LPs burned so far
+    pub burned_lp: u64,
+    /// Input tokens paid to the tickets so far
+    pub claimed_amount: u64,
+    /// LPs of the tickets paid in full
+    pub settled_lp: u64,
+}
+
+impl WithdrawEpochAccount {
+    pub const SIZE: usize = 1 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8;
+
+    /// Check if the epoch still accepts tickets
+    pub fn is_open(&self) -> bool {
+        self.closed_at == 0
+    }
+
+    /// Check if every ticket of the epoch can be paid in full
+    pub fn is_filled(&self) -> bool {
+        !self.is_open() && self.filled_amount == self.owed_amount
+    }
+
+    /// Add a ticket to the epoch
+    pub fn add_ticket(&mut self, lp_amount: u64, amount: u64, now: i64) -> Result<()> {
+        require!(self.is_open(), ErrorCode::WithdrawEpochClosed);
+
+        if self.requested_lp == 0 {
+            self.opened_at = now;
+        }
+        self.requested_lp = self
+            .requested_lp
+            .checked_add(lp_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        self.requested_amount = self
+            .requested_amount
+            .checked_add(amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        Ok(())
+    }
+
+    /// Remove a cancelled ticket from the epoch
+    pub fn remove_ticket(&mut self, lp_amount: u64, amount: u64) -> Result<()> {
+        require!(self.is_open(), ErrorCode::WithdrawEpochClosed);
+
+        self.requested_lp = self
+            .requested_lp
+            .checked_sub(lp_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        self.requested_amount = self
+            .requested_amount
+            .checked_sub(amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        Ok(())
+    }
+
+    /// Stop accepting tickets. Losses since the tickets were opened are shared with the vault,
+    /// paying at most the current value of the locked LPs
+    pub fn finalize(&mut self, lp_price: &LpPrice, now: i64) -> Result<()> {
+        require!(self.is_open(), ErrorCode::WithdrawEpochClosed);
+        require!(self.requested_lp > 0, ErrorCode::InvalidTicketAmount);
+
+        self.owed_amount = std::cmp::min(
+            self.requested_amount,
+            lp_price.lp_to_token(self.requested_lp)?,
+        );
+        self.closed_at = now;
+
+        Ok(())
+    }
+
+    /// Fill the epoch with up to the available amount, returning the amount set aside for the
+    /// tickets and the locked LPs to be burned
+    pub fn fill(&mut self, available_amount: u64) -> Result<(u64, u64)> {
+        require!(!self.is_open(), ErrorCode::WithdrawEpochOpen);
+
+        let outstanding = self
+            .owed_amount
+            .checked_sub(self.filled_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        let amount = std::cmp::min(outstanding, available_amount);
+
+        self.filled_amount = self
+            .filled_amount
+            .checked_add(amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        // The last fill burns the remaining LPs to avoid leaving rounding dust
+        let lp_amount = if self.is_filled() {
+            self.requested_lp
+                .checked_sub(self.burned_lp)
+                .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+        } else {
+            mul_div(self.requested_lp, amount, self.owed_amount)?
+        };
+        self.burned_lp = self
+            .burned_lp
+            .checked_add(lp_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        Ok((amount, lp_amount))
+    }
+
+    /// Record the payment of the claimed amount of the ticket, before emptying it. Once every
+    /// ticket of a filled epoch is paid, returns the rounding dust left of the filled amount
+    pub fn claim(&mut self, ticket: &WithdrawTicketAccount, amount: u64) -> Result<u64> {
+        self.claimed_amount = self
+            .claimed_amount
+            .checked_add(amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        if !self.is_filled() {
+            return Ok(0);
+        }
+
+        self.settled_lp = self
+            .settled_lp
+            .checked_add(ticket.lp_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        if self.settled_lp < self.requested_lp {
+            return Ok(0);
+        }
+
+        let dust = self
+            .filled_amount
+            .checked_sub(self.claimed_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        self.claimed_amount = self.filled_amount;
+        Ok(dust)
+    }
+
+    /// Input tokens of the ticket that can be paid with the amount filled so far
+    pub fn claimable_amount(&self, ticket: &WithdrawTicketAccount) -> Result<u64> {
+        if self.is_open() {
+            return Ok(0);
+        }
+
+        let paid = mul_div(ticket.amount, self.filled_amount, self.requested_amount)?;
+        paid.checked_sub(ticket.claimed_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))
+    }
+}
+
+/// Withdrawal ticket of a user, queued in an epoch
+#[account]
+#[derive(Default)]
+pub struct WithdrawTicketAccount {
+    /// PDA bump seed
+    pub bump: u8,
+    /// Vault address
+    pub vault: Pubkey,
+    /// Ticket owner
+    pub owner: Pubkey,
+    /// Epoch in which the ticket was opened
+    pub epoch: u64,
+    /// Timestamp of the last time the ticket was opened
+    pub opened_at: i64,
+    /// LP price of the last time the ticket was opened
+    pub lp_price: LpPrice,
+    /// Locked LPs
+    pub lp_amount: u64,
+    /// Input tokens requested, valued at the LP price when opened
+    pub amount: u64,
+    /// Input tokens already paid
+    pub claimed_amount: u64,
+}
+
+impl WithdrawTicketAccount {
+    pub const SIZE: usize = 1 + 32 + 32 + 8 + 8 + LpPrice::SIZE + 8 + 8 + 8;
+
+    /// Check if the ticket has no locked LPs
+    pub fn is_empty(&self) -> bool {
+        self.lp_amount == 0
+    }
+
+    /// Lock the LPs in the ticket at the given price, returning the requested input tokens. A
+    /// ticket can only be increased within its epoch
+    pub fn open(&mut self, epoch: u64, lp_amount: u64, lp_price: LpPrice, now: i64) -> Result<u64> {
+        if self.is_empty() {
+            self.epoch = epoch;
+            self.claimed_amount = 0;
+        }
+        require!(self.epoch == epoch, ErrorCode::TicketInPreviousEpoch);
+
+        let amount = lp_price.lp_to_token(lp_amount)?;
+        require!(amount > 0, ErrorCode::InvalidTicketAmount);
+
+        self.lp_amount = self
+            .lp_amount
+            .checked_add(lp_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        self.amount = self
+            .amount
+            .checked_add(amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        self.lp_price = lp_price;
+        self.opened_at = now;
+
+        Ok(amount)
+    }
+
+    /// Unlock part of the LPs of the ticket, returning the proportional input tokens removed from
+    /// the request
+    pub fn cancel(&mut self, lp_amount: u64) -> Result<u64> {
+        require!(
+            lp_amount > 0 && lp_amount <= self.lp_amount,
+            ErrorCode::InvalidTicketAmount
+        );
+
+        let amount = if lp_amount == self.lp_amount {
+            self.amount
+        } else {
+            mul_div(self.amount, lp_amount, self.lp_amount)?
+        };
+
+        self.lp_amount = self
+            .lp_amount
+            .checked_sub(lp_amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        self.amount = self
+            .amount
+            .checked_sub(amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        Ok(amount)
+    }
+
+    /// Record the payment of the claimed amount, emptying the ticket once the epoch is filled
+    /// (even if the rounded down payout left nothing to claim)
+    pub fn claim(&mut self, amount: u64, epoch_filled: bool) -> Result<()> {
+        self.claimed_amount = self
+            .claimed_amount
+            .checked_add(amount)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        if epoch_filled {
+            self.lp_amount = 0;
+            self.amount = 0;
+            self.claimed_amount = 0;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute `a * b / c` rounding down
+fn mul_div(a: u64, b: u64, c: u64) -> Result<u64> {
+    (a as u128)
+        .checked_mul(b as u128)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+        .checked_div(c as u128)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+        .try_into()
+        .map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn price(total_tokens: u64, minted_tokens: u64) -> LpPrice {
+        LpPrice {
+            total_tokens,
+            minted_tokens,
+        }
+    }
+
+    fn open_ticket(
+        epoch: &mut WithdrawEpochAccount,
+        lp_amount: u64,
+        lp_price: LpPrice,
+    ) -> WithdrawTicketAccount {
+        let mut ticket = WithdrawTicketAccount::default();
+        let amount = ticket.open(epoch.epoch, lp_amount, lp_price, 1).unwrap();
+        epoch.add_ticket(lp_amount, amount, 1).unwrap();
+        ticket
+    }
+
+    #[test]
+    fn test_withdraw_epoch_full_fill() {
+        let mut epoch = WithdrawEpochAccount::default();
+        let ticket_a = open_ticket(
+            &mut epoch,
+            1_000_000_000,
+            price(1_000_000_000, 1_000_000_000),
+        );
+        let ticket_b = open_ticket(
+            &mut epoch,
+            1_000_000_000,
+            price(2_000_000_000, 1_000_000_000),
+        );
+        assert_eq!(epoch.requested_lp, 2_000_000_000);
+        assert_eq!(epoch.requested_amount, 2_999_000_999);
+
+        // Nothing can be claimed nor filled while the epoch is open
+        assert_eq!(epoch.claimable_amount(&ticket_a).unwrap(), 0);
+        assert!(epoch.fill(1_000).is_err());
+
+        epoch
+            .finalize(&price(2_000_000_000, 1_000_000_000), 2)
+            .unwrap();
+        assert_eq!(epoch.owed_amount, 2_999_000_999);
+        assert!(epoch.add_ticket(1, 1, 3).is_err());
+
+        assert_eq!(
+            epoch.fill(u64::MAX).unwrap(),
+            (2_999_000_999, 2_000_000_000)
+        );
+        assert!(epoch.is_filled());
+        assert_eq!(epoch.claimable_amount(&ticket_a).unwrap(), 1_000_000_000);
+        assert_eq!(epoch.claimable_amount(&ticket_b).unwrap(), 1_999_000_999);
+    }
+
+    #[test]
+    fn test_withdraw_epoch_pro_rata() {
+        let mut epoch = WithdrawEpochAccount::default();
+        let mut ticket_a = open_ticket(
+            &mut epoch,
+            3_000_000_000,
+            price(1_000_000_000, 1_000_000_000),
+        );
+        let ticket_b = open_ticket(
+            &mut epoch,
+            1_000_000_000,
+            price(1_000_000_000, 1_000_000_000),
+        );
+        epoch
+            .finalize(&price(1_000_000_000, 1_000_000_000), 2)
+            .unwrap();
+
+        // A quarter of the liquidity is available
+        assert_eq!(
+            epoch.fill(1_000_000_000).unwrap(),
+            (1_000_000_000, 1_000_000_000)
+        );
+        assert!(!epoch.is_filled());
+        assert_eq!(epoch.claimable_amount(&ticket_a).unwrap(), 750_000_000);
+        assert_eq!(epoch.claimable_amount(&ticket_b).unwrap(), 250_000_000);
+
+        ticket_a.claim(750_000_000, epoch.is_filled()).unwrap();
+        assert_eq!(epoch.claimable_amount(&ticket_a).unwrap(), 0);
+
+        // The rest of the epoch is filled later
+        assert_eq!(
+            epoch.fill(u64::MAX).unwrap(),
+            (3_000_000_000, 3_000_000_000)
+        );
+        assert!(epoch.is_filled());
+        assert_eq!(epoch.claimable_amount(&ticket_a).unwrap(), 2_250_000_000);
+        assert_eq!(epoch.claimable_amount(&ticket_b).unwrap(), 1_000_000_000);
+
+        ticket_a.claim(2_250_000_000, epoch.is_filled()).unwrap();
+        assert!(ticket_a.is_empty());
+        assert_eq!(ticket_a.claimed_amount, 0);
+    }
+
+    #[test]
+    fn test_withdraw_epoch_loss() {
+        let mut epoch = WithdrawEpochAccount::default();
+        let ticket = open_ticket(
+            &mut epoch,
+            1_000_000_000,
+            price(1_000_000_000, 1_000_000_000),
+        );
+
+        // The LP price halves before the epoch is closed
+        epoch
+            .finalize(&price(500_000_000, 1_000_000_000), 2)
+            .unwrap();
+        assert_eq!(epoch.owed_amount, 500_499_500);
+
+        assert_eq!(epoch.fill(u64::MAX).unwrap(), (500_499_500, 1_000_000_000));
+        assert_eq!(epoch.claimable_amount(&ticket).unwrap(), 500_499_500);
+    }
+
+    #[test]
+    fn test_withdraw_epoch_loss_dust() {
+        let mut epoch = WithdrawEpochAccount::default();
+        let mut ticket_a = open_ticket(&mut epoch, 1, price(1_000, 1_000));
+        let _ticket_b = open_ticket(&mut epoch, 1_000_000, price(1_000, 1_000));
+        assert_eq!(ticket_a.amount, 1);
+
+        // The LP price halves before the epoch is closed
+        epoch.finalize(&price(500, 1_000), 2).unwrap();
+        epoch.fill(u64::MAX).unwrap();
+        assert!(epoch.is_filled());
+
+        // The loss floors the payout of the smallest ticket to zero, which still empties it
+        assert_eq!(epoch.claimable_amount(&ticket_a).unwrap(), 0);
+        ticket_a.claim(0, epoch.is_filled()).unwrap();
+        assert!(ticket_a.is_empty());
+        assert_eq!(ticket_a.amount, 0);
+
+        ticket_a.open(1, 1_000, price(1_000, 1_000), 3).unwrap();
+        assert_eq!(ticket_a.epoch, 1);
+    }
+
+    #[test]
+    fn test_withdraw_epoch_dust_release() {
+        let mut epoch = WithdrawEpochAccount::default();
+        let lp_price = price(1_000_000_000, 1_000_000_000);
+        let mut tickets = [
+            open_ticket(&mut epoch, 1_000_000_000, lp_price),
+            open_ticket(&mut epoch, 1_000_000_000, lp_price),
+            open_ticket(&mut epoch, 1_000_000_000, lp_price),
+        ];
+
+        // The LP price drops before the epoch is closed, so the payouts are rounded down
+        epoch
+            .finalize(&price(2_000_000_000, 3_000_000_000), 2)
+            .unwrap();
+        let (filled, _) = epoch.fill(u64::MAX).unwrap();
+
+        let mut claimed = 0;
+        let mut dust = 0;
+        for ticket in tickets.iter_mut() {
+            assert_eq!(dust, 0);
+            let amount = epoch.claimable_amount(ticket).unwrap();
+            dust = epoch.claim(ticket, amount).unwrap();
+            ticket.claim(amount, epoch.is_filled()).unwrap();
+            claimed += amount;
+        }
+
+        // The dust is released once the last ticket is paid
+        assert!(dust > 0);
+        assert_eq!(claimed + dust, filled);
+        assert_eq!(epoch.claimed_amount, filled);
+        assert_eq!(epoch.settled_lp, epoch.requested_lp);
+    }
+
+    #[test]
+    fn test_withdraw_ticket_epochs() {
+        let mut ticket = WithdrawTicketAccount::default();
+        ticket.open(0, 1_000, price(1_000, 1_000), 1).unwrap();
+        ticket.open(0, 1_000, price(1_000, 1_000), 2).unwrap();
+        assert_eq!(ticket.lp_amount, 2_000);
+        assert_eq!(ticket.opened_at, 2);
+
+        // Tickets in a previous epoch must be claimed before opening a new one
+        assert!(ticket.open(1, 1_000, price(1_000, 1_000), 3).is_err());
+
+        ticket.claim(2_000, true).unwrap();
+        ticket.open(1, 1_000, price(1_000, 1_000), 3).unwrap();
+        assert_eq!(ticket.epoch, 1);
+
+        assert!(ticket.open(1, 0, price(1_000, 1_000), 4).is_err());
+    }
+
+    #[test]
+    fn test_cancel_withdraw_ticket() {
+        let mut epoch = WithdrawEpochAccount::default();
+        let mut ticket_a = open_ticket(&mut epoch, 3_000, price(1_000, 1_000));
+        let _ticket_b = open_ticket(&mut epoch, 1_000, price(1_000, 1_000));
+
+        assert!(ticket_a.cancel(0).is_err());
+        assert!(ticket_a.cancel(3_001).is_err());
+
+        let amount = ticket_a.cancel(1_000).unwrap();
+        epoch.remove_ticket(1_000, amount).unwrap();
+        assert_eq!(ticket_a.lp_amount, 2_000);
+        assert_eq!(ticket_a.amount + amount, 3_000);
+        assert_eq!(epoch.requested_lp, 3_000);
+        assert_eq!(epoch.requested_amount, 4_000 - amount);
+
+        // The rest of the ticket is cancelled without leaving rounding dust
+        let amount = ticket_a.cancel(2_000).unwrap();
+        epoch.remove_ticket(2_000, amount).unwrap();
+        assert!(ticket_a.is_empty());
+        assert_eq!(ticket_a.amount, 0);
+        assert_eq!(epoch.requested_lp, 1_000);
+
+        // Tickets can no longer be cancelled once the epoch is closed
+        epoch.finalize(&price(1_000, 1_000), 2).unwrap();
+        assert!(epoch.remove_ticket(1_000, 1_000).is_err());
+    }
+}
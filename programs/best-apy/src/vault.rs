@@ -14,6 +14,42 @@ pub const _VAULT_VERSION: u8 = 1;
 #[constant]
 pub const WEIGHTS_SCALE: u32 = 10_000;
 
+/// Virtual tokens and LPs added to the LP price conversions. The first deposit is still minted
+/// 1:1, but inflating the LP price by donating to the vault is only profitable after donating
+/// `VIRTUAL_LP_OFFSET` times the amount to steal, most of which goes to the virtual LPs
+#[constant]
+pub const VIRTUAL_LP_OFFSET: u64 = 1_000_000;
+
+/// Scale of the fees, given in basis points
+#[constant]
+pub const FEES_SCALE: u16 = 10_000;
+
+/// Maximum performance fee (in basis points)
+#[constant]
+pub const MAX_PERFORMANCE_FEE_BPS: u16 = 3_000;
+
+/// Maximum annual management fee (in basis points)
+#[constant]
+pub const MAX_MANAGEMENT_FEE_BPS: u16 = 500;
+
+/// Maximum deposit fee (in basis points)
+#[constant]
+pub const MAX_DEPOSIT_FEE_BPS: u16 = 100;
+
+/// Maximum withdrawal fee (in basis points)
+#[constant]
+pub const MAX_WITHDRAW_FEE_BPS: u16 = 100;
+
+/// Maximum bounty paid to the keeper settling a withdrawal ticket (in basis points)
+#[constant]
+pub const MAX_SETTLE_BOUNTY_BPS: u16 = 50;
+
+/// Number of steps in which the invested TVL is allocated when maximizing the supply yield
+pub const SUPPLY_RATE_ALLOCATION_STEPS: u32 = 100;
+
+/// Seconds in a year, used to accrue the management fee
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
 /// Strategy vault account
 #[account]
 #[derive(Default)]
@@ -21,8 +57,8 @@ pub struct VaultAccount {
     /// Vault version
     pub version: u8,
 
-    /// This vault is paused
-    pub is_paused: bool,
+    /// Operations paused in this vault (bitfield of `PauseFlags`, a legacy value of 1 pauses all)
+    pub paused: u8,
 
     /// Account seed number
     pub seed_number: u8,
@@ -51,9 +87,34 @@ pub struct VaultAccount {
     /// Bump seed for the ticket mint address
     pub bump_ticket_mint: u8,
 
+    /// Vault fees
+    pub fees: FeeParams,
+
+    /// Unrecognized loss found when reconciling the vault holdings (not accounted in current_tvl)
+    pub pending_loss: u64,
+
+    /// Withdrawal epoch accepting new tickets
+    pub withdraw_epoch: u64,
+    /// Oldest withdrawal epoch not completely filled
+    pub pending_withdraw_epoch: u64,
+    /// Idle funds set aside for the filled withdrawal tickets (not accounted in current_tvl)
+    pub reserved_for_tickets: u64,
+    /// Bounty paid out of a ticket to the keeper settling it on behalf of the owner (in basis
+    /// points)
+    pub settle_bounty_bps: u16,
+    /// Percentage of the TVL kept idle in the vault as withdrawal buffer
+    pub idle_weight: u32,
+
+    /// Maximum share of a protocol reserve total supply owned by the vault (in basis points,
+    /// zero if uncapped)
+    pub max_reserve_share_bps: u16,
+    /// Algorithm used to update the protocol weights on every refresh
+    pub weight_strategy: WeightStrategy,
+    /// Limits on the weight changes and the rebalanced amounts
+    pub damping: DampingParams,
+
     /// Additional padding
-    pub _padding1: [u8; 7],
-    pub _padding2: [u64; 7],
+    pub _padding: [u8; 3],
 
     /// Protocol data (maximum = 10)
     pub protocols: Vec<ProtocolData>,
@@ -71,13 +132,25 @@ impl VaultAccount {
         + 8
         + 8
         + LpPrice::SIZE
-        + 8 * 8
+        + 1
+        + FeeParams::SIZE
+        + 8
+        + 8
+        + 8
+        + 8
+        + 2
+        + 4
+        + 2
+        + 1
+        + DampingParams::SIZE
+        + 3
         + 4
         + ProtocolData::SIZE * 10;
 
     /// Initialize a new vault
     pub fn init(params: InitVaultAccountParams) -> Self {
         Self {
+            seed_number: params.seed_number,
             bumps: params.bumps,
             input_mint_pubkey: params.input_mint_pubkey,
             dao_treasury_lp_token_account: params.dao_treasury_lp_token_account,
@@ -89,6 +162,16 @@ impl VaultAccount {
         }
     }
 
+    /// Operations paused in the vault
+    pub fn pause_flags(&self) -> PauseFlags {
+        PauseFlags::from_bits(self.paused)
+    }
+
+    /// Set the operations paused in the vault
+    pub fn set_pause_flags(&mut self, flags: PauseFlags) {
+        self.paused = flags.bits();
+    }
+
     /// Find the position of the protocol in the protocol_data vector
     pub fn protocol_position(&self, protocol: Protocols) -> Result<usize> {
         let protocol_id: u8 = (protocol as usize).try_into().unwrap();
@@ -196,45 +279,353 @@ impl VaultAccount {
                 }
             }
 
-            // Renormalize the weights
-            let (max_indx, max_protocol) = self
-                .protocols
+            self.normalize_protocol_weights()?;
+        }
+
+        Ok(())
+    }
+
+    /// Make the weights add up to the total, giving the difference to the protocol with the
+    /// largest weight
+    fn normalize_protocol_weights(&mut self) -> Result<()> {
+        let (max_indx, max_protocol) = self
+            .protocols
+            .iter()
+            .enumerate()
+            .max_by_key(|&(_, protocol)| protocol.weight)
+            .unwrap();
+
+        let weights_sum: u32 = self
+            .protocols
+            .iter()
+            .try_fold(0_u32, |acc, &protocol| acc.checked_add(protocol.weight))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        self.protocols[max_indx].weight = WEIGHTS_SCALE
+            .checked_sub(
+                weights_sum
+                    .checked_sub(max_protocol.weight)
+                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
+            )
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        Ok(())
+    }
+
+    /// Update the protocol weights with the vault weight strategy and the protocol caps, limiting
+    /// the change of each weight to the maximum weight delta
+    pub fn refresh_protocol_weights(&mut self) -> Result<()> {
+        let previous_weights: Vec<u32> = self
+            .protocols
+            .iter()
+            .map(|protocol| protocol.weight)
+            .collect();
+        self.update_weights_with_strategy()?;
+        // The caps apply once for every strategy, even when it keeps the previous weights
+        self.cap_protocol_weights()?;
+        self.limit_weight_changes(&previous_weights)
+    }
+
+    /// Update the protocol weights with the vault weight strategy
+    fn update_weights_with_strategy(&mut self) -> Result<()> {
+        match self.weight_strategy {
+            WeightStrategy::Auto => self.update_protocol_weights(),
+            WeightStrategy::SupplyRate => self.update_protocol_weights_from_supply_rates(),
+            WeightStrategy::Manual => Ok(()),
+            WeightStrategy::EqualWeight => {
+                let scores = self
+                    .protocols
+                    .iter()
+                    .map(|protocol| u128::from(protocol.is_active()))
+                    .collect::<Vec<u128>>();
+                self.update_protocol_weights_from_scores(&scores)
+            }
+            WeightStrategy::MaxYield => {
+                let yields = self
+                    .protocols
+                    .iter()
+                    .map(|protocol| protocol.rewards.rewards_per_deposit_wad())
+                    .collect::<Result<Vec<u128>>>()?;
+                let best = yields
+                    .iter()
+                    .zip(self.protocols.iter())
+                    .enumerate()
+                    .filter(|(_, (_, protocol))| protocol.is_active())
+                    .max_by_key(|&(_, (&protocol_yield, _))| protocol_yield)
+                    .map(|(i, _)| i);
+                let scores = yields
+                    .iter()
+                    .enumerate()
+                    .map(
+                        |(i, &protocol_yield)| {
+                            if Some(i) == best {
+                                protocol_yield
+                            } else {
+                                0
+                            }
+                        },
+                    )
+                    .collect::<Vec<u128>>();
+                self.update_protocol_weights_from_scores(&scores)
+            }
+            WeightStrategy::RiskAdjusted => {
+                let scores = self
+                    .protocols
+                    .iter()
+                    .map(|protocol| {
+                        Ok(protocol.rewards.rewards_per_deposit_wad()?
+                            / cmp::max(protocol.risk_score, 1) as u128)
+                    })
+                    .collect::<Result<Vec<u128>>>()?;
+                self.update_protocol_weights_from_scores(&scores)
+            }
+        }
+    }
+
+    /// Limit the change of every protocol weight from its previous value to the maximum weight
+    /// delta. The increases are scaled down to fit in the weight released by the decreases and the
+    /// weight previously kept idle, so that the weights never add up to more than the total. A
+    /// protocol cap is never exceeded to limit a decrease
+    pub fn limit_weight_changes(&mut self, previous_weights: &[u32]) -> Result<()> {
+        let max_delta = self.damping.max_weight_delta as u32;
+        if max_delta == 0 {
+            return Ok(());
+        }
+
+        let previous_sum = previous_weights
+            .iter()
+            .try_fold(0_u32, |acc, &x| acc.checked_add(x))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        let mut released = WEIGHTS_SCALE.saturating_sub(previous_sum);
+        let mut increased = 0_u32;
+
+        for (protocol, &previous) in self.protocols.iter_mut().zip(previous_weights.iter()) {
+            if protocol.weight < previous {
+                protocol.weight = cmp::max(protocol.weight, previous.saturating_sub(max_delta));
+                released += previous - protocol.weight;
+            } else {
+                protocol.weight = cmp::min(protocol.weight, previous + max_delta);
+                increased += protocol.weight - previous;
+            }
+        }
+
+        if increased > released {
+            for (protocol, &previous) in self.protocols.iter_mut().zip(previous_weights.iter()) {
+                if protocol.weight > previous {
+                    let increase =
+                        (protocol.weight - previous) as u64 * released as u64 / increased as u64;
+                    protocol.weight = previous + increase as u32;
+                }
+            }
+        }
+
+        // The caps win over the damping, keeping the excess idle
+        let invested_tvl = self.invested_tvl()?;
+        for protocol in self.protocols.iter_mut() {
+            protocol.weight = cmp::min(protocol.weight, protocol.weight_cap(invested_tvl)?);
+        }
+
+        Ok(())
+    }
+
+    /// Minimum weight of every active protocol, so that they all fit in the total
+    fn active_minimum_weight(&self, invested_tvl: u64) -> Result<u32> {
+        let active_protocols = self
+            .protocols
+            .iter()
+            .filter(|protocol| protocol.is_active())
+            .count() as u32;
+
+        Ok(cmp::min(
+            self.minimum_weight(invested_tvl.into())?,
+            WEIGHTS_SCALE
+                .checked_div(active_protocols)
+                .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
+        ))
+    }
+
+    /// Set the weights of the active protocols in proportion to their scores, keeping at least
+    /// the minimum weight for each of them. The weights are not updated if no active protocol has
+    /// a positive score
+    pub fn update_protocol_weights_from_scores(&mut self, scores: &[u128]) -> Result<()> {
+        let invested_tvl = self.invested_tvl()?;
+        let total_score = self
+            .protocols
+            .iter()
+            .zip(scores.iter())
+            .filter(|(protocol, _)| protocol.is_active())
+            .try_fold(0_u128, |acc, (_, &score)| acc.checked_add(score))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        if invested_tvl == 0 || total_score == 0 {
+            return Ok(());
+        }
+
+        let min_weight = self.active_minimum_weight(invested_tvl)?;
+        for (protocol, &score) in self.protocols.iter_mut().zip(scores.iter()) {
+            if protocol.is_active() {
+                let weight: u32 = score
+                    .checked_mul(WEIGHTS_SCALE.into())
+                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+                    .checked_div(total_score)
+                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+                    .try_into()
+                    .map_err(|_| ErrorCode::MathOverflow)?;
+                protocol.weight = cmp::max(min_weight, weight);
+            }
+        }
+
+        self.normalize_protocol_weights()
+    }
+
+    /// Update the weights of the active protocols maximizing the yield expected from the last
+    /// reserve snapshots, considering how the vault deposit lowers the utilization of each reserve.
+    /// Every active protocol keeps the minimum weight, and the rest of the invested TVL is
+    /// allocated in steps, each one to the protocol with the highest marginal yield
+    pub fn update_protocol_weights_from_supply_rates(&mut self) -> Result<()> {
+        let invested_tvl = self.invested_tvl()?;
+        if invested_tvl == 0 || !self.protocols.iter().any(|protocol| protocol.is_active()) {
+            return Ok(());
+        }
+
+        let min_weight = self.active_minimum_weight(invested_tvl)?;
+        let caps = self
+            .protocols
+            .iter()
+            .map(|protocol| protocol.weight_cap(invested_tvl))
+            .collect::<Result<Vec<u32>>>()?;
+
+        let mut weights: Vec<u32> = self
+            .protocols
+            .iter()
+            .zip(caps.iter())
+            .map(|(protocol, &cap)| {
+                if protocol.is_active() {
+                    cmp::min(min_weight, cap)
+                } else {
+                    0
+                }
+            })
+            .collect();
+
+        let step = WEIGHTS_SCALE / SUPPLY_RATE_ALLOCATION_STEPS;
+        let mut remaining = WEIGHTS_SCALE
+            .checked_sub(weights.iter().sum())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        // Only the marginal yield of the protocol receiving the last step changes
+        let mut marginal_yields = self
+            .protocols
+            .iter()
+            .enumerate()
+            .map(|(i, protocol)| protocol.marginal_yield(invested_tvl, weights[i], caps[i], step))
+            .collect::<Result<Vec<Option<u128>>>>()?;
+
+        while remaining > 0 {
+            let best = marginal_yields
                 .iter()
                 .enumerate()
-                .max_by_key(|&(_, protocol)| protocol.weight)
-                .unwrap();
+                .filter_map(|(i, marginal_yield)| marginal_yield.map(|y| (i, y)))
+                .fold(None, |best: Option<(usize, u128)>, (i, y)| match best {
+                    Some((_, best_y)) if best_y >= y => best,
+                    _ => Some((i, y)),
+                });
+
+            // The weight not fitting below the caps is kept idle
+            let i = match best {
+                Some((i, _)) => i,
+                None => break,
+            };
+
+            let added = cmp::min(cmp::min(step, remaining), caps[i] - weights[i]);
+            weights[i] += added;
+            remaining -= added;
+            marginal_yields[i] =
+                self.protocols[i].marginal_yield(invested_tvl, weights[i], caps[i], step)?;
+        }
+
+        for (protocol, weight) in self.protocols.iter_mut().zip(weights) {
+            protocol.weight = weight;
+        }
+
+        Ok(())
+    }
+
+    /// Limit the weights of the active protocols to their caps, spreading the excess over the
+    /// protocols below their caps in proportion to their weights. The excess that does not fit in
+    /// any protocol is kept idle
+    pub fn cap_protocol_weights(&mut self) -> Result<()> {
+        let invested_tvl = self.invested_tvl()?;
+        let caps = self
+            .protocols
+            .iter()
+            .map(|protocol| protocol.weight_cap(invested_tvl))
+            .collect::<Result<Vec<u32>>>()?;
+
+        // Every round either spreads the whole excess or caps one more protocol
+        let mut excess = 0_u32;
+        loop {
+            for (protocol, &cap) in self.protocols.iter_mut().zip(caps.iter()) {
+                if protocol.is_active() && protocol.weight > cap {
+                    excess = excess
+                        .checked_add(protocol.weight - cap)
+                        .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+                    protocol.weight = cap;
+                }
+            }
 
-            let weights_sum: u32 = self
+            let uncapped_weight = self
                 .protocols
                 .iter()
-                .try_fold(0_u32, |acc, &protocol| acc.checked_add(protocol.weight))
+                .zip(caps.iter())
+                .filter(|(protocol, &cap)| protocol.is_active() && protocol.weight < cap)
+                .try_fold(0_u32, |acc, (protocol, _)| acc.checked_add(protocol.weight))
                 .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
 
-            self.protocols[max_indx].weight = WEIGHTS_SCALE
-                .checked_sub(
-                    weights_sum
-                        .checked_sub(max_protocol.weight)
-                        .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
-                )
-                .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+            if excess == 0 || uncapped_weight == 0 {
+                break;
+            }
+
+            let mut spread = 0_u32;
+            for (protocol, &cap) in self.protocols.iter_mut().zip(caps.iter()) {
+                if protocol.is_active() && protocol.weight < cap {
+                    let added = (excess as u64)
+                        .checked_mul(protocol.weight as u64)
+                        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+                        .checked_div(uncapped_weight as u64)
+                        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+                        as u32;
+                    protocol.weight += added;
+                    spread += added;
+                }
+            }
+
+            // Rounding dust goes to the first protocol below its cap
+            if spread == 0 {
+                let (protocol, _) = self
+                    .protocols
+                    .iter_mut()
+                    .zip(caps.iter())
+                    .find(|(protocol, &cap)| protocol.is_active() && protocol.weight < cap)
+                    .unwrap();
+                protocol.weight += excess;
+                spread = excess;
+            }
+            excess -= spread;
         }
 
         Ok(())
     }
 
-    /// Calculate amount to deposit in the given protocol
+    /// Calculate amount to deposit in the given protocol, limited to the available idle funds
+    /// above the idle buffer
     pub fn calculate_deposit(&self, protocol_idx: usize, available_amount: u64) -> Result<u64> {
-        let protocol = &self.protocols[protocol_idx];
-
-        let deposited_amount = protocol.amount;
-        let target_amount = protocol.amount_should_be_deposited(self.current_tvl)?;
-
-        if target_amount > deposited_amount {
-            let amount = target_amount
-                .checked_sub(deposited_amount)
-                .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        let amount = self.deposit_shortfall(protocol_idx)?;
 
-            Ok(cmp::min(amount, available_amount))
+        if amount > 0 {
+            // With other protocols above their targets, the shortfall can exceed the idle funds
+            // above the buffer
+            let spare_amount = available_amount.saturating_sub(self.idle_buffer()?);
+            Ok(cmp::min(amount, spare_amount))
         } else {
             err!(ErrorCode::InvalidProtocolDeposit)
         }
@@ -242,20 +633,246 @@ impl VaultAccount {
 
     /// Calculate amount to withdraw from the given protocol
     pub fn calculate_withdraw(&self, protocol_idx: usize) -> Result<u64> {
+        let amount = self.deposit_excess(protocol_idx)?;
+
+        if amount > 0 {
+            Ok(amount)
+        } else {
+            err!(ErrorCode::InvalidProtocolWithdraw)
+        }
+    }
+
+    /// Amount missing in the given protocol to reach its target (zero if already reached or
+    /// below the minimum deviation)
+    pub fn deposit_shortfall(&self, protocol_idx: usize) -> Result<u64> {
         let protocol = &self.protocols[protocol_idx];
+        let invested_tvl = self.invested_tvl()?;
+        let target_amount = protocol.target_amount(invested_tvl)?;
+        let amount = target_amount.saturating_sub(protocol.amount);
+        self.damping.above_min_deviation(amount, invested_tvl)
+    }
+
+    /// Amount exceeding the target of the given protocol (zero if not exceeded or below the
+    /// minimum deviation)
+    pub fn deposit_excess(&self, protocol_idx: usize) -> Result<u64> {
+        let protocol = &self.protocols[protocol_idx];
+        let invested_tvl = self.invested_tvl()?;
+        let target_amount = protocol.target_amount(invested_tvl)?;
+        let amount = protocol.amount.saturating_sub(target_amount);
+        self.damping.above_min_deviation(amount, invested_tvl)
+    }
 
-        let deposited_amount = protocol.amount;
-        let target_amount = protocol.amount_should_be_deposited(self.current_tvl)?;
+    /// Check and set the percentage of the TVL kept idle
+    pub fn set_idle_weight(&mut self, idle_weight: u32) -> Result<()> {
+        require!(idle_weight <= WEIGHTS_SCALE, ErrorCode::InvalidWeights);
+        self.idle_weight = idle_weight;
+        Ok(())
+    }
+
+    /// Amount of the TVL that should be kept idle in the vault
+    pub fn idle_buffer(&self) -> Result<u64> {
+        let amount: u64 = (self.current_tvl as u128)
+            .checked_mul(self.idle_weight as u128)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(WEIGHTS_SCALE.into())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        Ok(amount)
+    }
+
+    /// Amount of the TVL to be spread across the protocols according to their weights
+    pub fn invested_tvl(&self) -> Result<u64> {
+        self.current_tvl
+            .checked_sub(self.idle_buffer()?)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))
+    }
+
+    /// Add the last protocol rewards to the pending rewards. A net loss is taken from the pending
+    /// rewards and then recorded as pending loss, instead of being booked in the TVL
+    pub fn accumulate_protocol_rewards(&mut self) -> Result<()> {
+        let rewards = self
+            .protocols
+            .iter()
+            .try_fold(self.rewards_sum as i128, |acc, protocol| {
+                acc.checked_add(protocol.rewards.amount as i128)
+            })
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
 
-        if target_amount < deposited_amount {
-            let amount = deposited_amount
-                .checked_sub(target_amount)
+        if rewards >= 0 {
+            self.rewards_sum = rewards.try_into().map_err(|_| ErrorCode::MathOverflow)?;
+        } else {
+            let loss: u64 = rewards
+                .unsigned_abs()
+                .try_into()
+                .map_err(|_| ErrorCode::MathOverflow)?;
+            self.rewards_sum = 0;
+            self.pending_loss = self
+                .pending_loss
+                .checked_add(loss)
                 .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        }
 
-            Ok(amount)
+        Ok(())
+    }
+
+    /// Book the given amount of the pending loss in the TVL
+    pub fn recognize_loss(&mut self, loss: u64) -> Result<()> {
+        require!(
+            loss > 0 && loss <= self.pending_loss,
+            ErrorCode::InvalidLossAmount
+        );
+
+        self.pending_loss -= loss;
+        self.current_tvl = self
+            .current_tvl
+            .checked_sub(loss)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        Ok(())
+    }
+
+    /// LP price used to value withdrawals: the previous LP price discounted by the share of the
+    /// TVL lost but not yet recognized, so that withdrawing users do not leave the loss behind
+    pub fn withdraw_lp_price(&self) -> Result<LpPrice> {
+        let price = self.previous_lp_price;
+        if self.pending_loss == 0 || self.current_tvl == 0 {
+            return Ok(price);
+        }
+
+        let remaining_tvl = self.current_tvl.saturating_sub(self.pending_loss);
+        let total_tokens = (price.total_tokens as u128)
+            .checked_mul(remaining_tvl as u128)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(self.current_tvl as u128)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?;
+
+        Ok(LpPrice {
+            total_tokens,
+            minted_tokens: price.minted_tokens,
+        })
+    }
+
+    /// LP price used to value deposits given the LP supply: the current TVL without the loss not
+    /// yet recognized, so that depositing users do not buy into the loss of the existing holders
+    pub fn deposit_lp_price(&self, minted_tokens: u64) -> LpPrice {
+        LpPrice {
+            total_tokens: self.current_tvl.saturating_sub(self.pending_loss),
+            minted_tokens,
+        }
+    }
+
+    /// Idle funds of the given vault input token balance, excluding the ones reserved for the
+    /// withdrawal tickets
+    pub fn available_idle(&self, balance: u64) -> u64 {
+        balance.saturating_sub(self.reserved_for_tickets)
+    }
+
+    /// Take the amount paid to a withdrawal ticket out of the reserved funds, returning the
+    /// rounding dust left by a paid epoch to the TVL
+    pub fn pay_ticket(&mut self, amount: u64, dust: u64) -> Result<()> {
+        self.reserved_for_tickets = self
+            .reserved_for_tickets
+            .checked_sub(amount)
+            .and_then(|reserved| reserved.checked_sub(dust))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        self.current_tvl = self
+            .current_tvl
+            .checked_add(dust)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        Ok(())
+    }
+
+    /// Check and set the bounty paid to the keepers settling withdrawal tickets
+    pub fn set_settle_bounty(&mut self, bounty_bps: u16) -> Result<()> {
+        require!(bounty_bps <= MAX_SETTLE_BOUNTY_BPS, ErrorCode::InvalidFee);
+        self.settle_bounty_bps = bounty_bps;
+        Ok(())
+    }
+
+    /// Compute the bounty paid to the keeper out of the settled amount of a withdrawal ticket
+    pub fn settle_bounty(&self, amount: u64) -> Result<u64> {
+        bps_of(amount, self.settle_bounty_bps)
+    }
+
+    /// Check and set the maximum share of a protocol reserve owned by the vault
+    pub fn set_max_reserve_share(&mut self, max_reserve_share_bps: u16) -> Result<()> {
+        require!(
+            max_reserve_share_bps <= FEES_SCALE,
+            ErrorCode::InvalidReserveShare
+        );
+        self.max_reserve_share_bps = max_reserve_share_bps;
+        Ok(())
+    }
+
+    /// Maximum amount that can be deposited in the given protocol without owning more than the
+    /// maximum share of the reserve total supply (including the vault deposits)
+    pub fn max_reserve_deposit(&self, protocol_idx: usize, reserve_supply: u64) -> Result<u64> {
+        let share = self.max_reserve_share_bps as u128;
+        let scale = FEES_SCALE as u128;
+        if share == 0 || share == scale {
+            return Ok(u64::MAX);
+        }
+
+        // (owned + amount) * scale <= (supply + amount) * share
+        let max_owned = (reserve_supply as u128)
+            .checked_mul(share)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        let owned = (self.protocols[protocol_idx].amount as u128)
+            .checked_mul(scale)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let amount = max_owned.saturating_sub(owned) / (scale - share);
+        Ok(u64::try_from(amount).unwrap_or(u64::MAX))
+    }
+
+    /// Reconcile the vault holdings (idle tokens not reserved for the withdrawal tickets plus the
+    /// amounts deposited in the protocols) with the accounted TVL. A surplus is booked as rewards,
+    /// while a shortfall is first taken from the pending rewards and then recorded as pending
+    /// loss. Returns the holdings difference
+    pub fn reconcile_holdings(&mut self, balance: u64) -> Result<i128> {
+        let idle_amount = self.available_idle(balance);
+        let holdings = self
+            .protocols
+            .iter()
+            .try_fold(idle_amount as u128, |acc, protocol| {
+                acc.checked_add(protocol.amount as u128)
+            })
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let accounted = (self.current_tvl as u128)
+            .checked_add(self.rewards_sum as u128)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let diff = (holdings as i128)
+            .checked_sub(accounted as i128)
+            .and_then(|diff| diff.checked_add(self.pending_loss as i128))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        if diff >= 0 {
+            let surplus: u64 = diff.try_into().map_err(|_| ErrorCode::MathOverflow)?;
+            let recovered = cmp::min(surplus, self.pending_loss);
+            self.pending_loss -= recovered;
+            self.rewards_sum = self
+                .rewards_sum
+                .checked_add(surplus - recovered)
+                .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
         } else {
-            err!(ErrorCode::InvalidProtocolWithdraw)
+            let shortfall: u64 = diff
+                .unsigned_abs()
+                .try_into()
+                .map_err(|_| ErrorCode::MathOverflow)?;
+            let from_rewards = cmp::min(shortfall, self.rewards_sum);
+            self.rewards_sum -= from_rewards;
+            self.pending_loss = self
+                .pending_loss
+                .checked_add(shortfall - from_rewards)
+                .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
         }
+
+        Ok(diff)
     }
 }
 
@@ -282,41 +899,361 @@ impl Bumps {
     pub const SIZE: usize = 1 + 1;
 }
 
-/// Strategy refresh parameters
-#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
-pub struct RefreshParams {
-    /// Minimum elapsed slots for updating the protocol weights
-    pub min_elapsed_time: i64,
-    /// Minimum amount of lamports to deposit in each protocol
-    pub min_deposit_lamports: u64,
-}
-
-impl RefreshParams {
-    pub const SIZE: usize = 8 + 8;
+/// Operations which can be paused independently in each vault
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default, Debug)]
+pub struct PauseFlags {
+    /// Deposits into the vault
+    pub deposit: bool,
+    /// Withdrawals from the vault (including closing tickets)
+    pub withdraw: bool,
+    /// Opening withdrawal tickets
+    pub open_ticket: bool,
+    /// Moving funds between the vault and the protocols
+    pub rebalance: bool,
 }
 
-/// Protocol data
-#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
-pub struct ProtocolData {
-    /// Protocol ID
-    pub protocol_id: u8,
-
-    /// Hashes of Pubkey
-    pub hash_pubkey: HashPubkey,
+impl PauseFlags {
+    /// Legacy `is_paused` flag, pausing every operation
+    pub const ALL: u8 = 1 << 0;
+    pub const DEPOSIT: u8 = 1 << 1;
+    pub const WITHDRAW: u8 = 1 << 2;
+    pub const OPEN_TICKET: u8 = 1 << 3;
+    pub const REBALANCE: u8 = 1 << 4;
+
+    /// Decode the flags stored in the vault
+    pub fn from_bits(bits: u8) -> Self {
+        let all = bits & Self::ALL != 0;
+        Self {
+            deposit: all || bits & Self::DEPOSIT != 0,
+            withdraw: all || bits & Self::WITHDRAW != 0,
+            open_ticket: all || bits & Self::OPEN_TICKET != 0,
+            rebalance: all || bits & Self::REBALANCE != 0,
+        }
+    }
 
-    /// Percentage of the TVL that should be deposited in the protocol
-    pub weight: u32,
-    /// Deposited token amount in the protocol
-    pub amount: u64,
-    /// Accumulated rewards
-    pub rewards: AccumulatedRewards,
+    /// Encode the flags to be stored in the vault
+    pub fn bits(&self) -> u8 {
+        let mut bits = 0;
+        if self.deposit {
+            bits |= Self::DEPOSIT;
+        }
+        if self.withdraw {
+            bits |= Self::WITHDRAW;
+        }
+        if self.open_ticket {
+            bits |= Self::OPEN_TICKET;
+        }
+        if self.rebalance {
+            bits |= Self::REBALANCE;
+        }
+        bits
+    }
 
-    /// Padding for other future field
-    pub _padding: [u64; 5],
+    /// Check every operation paused in other is also paused in self
+    pub fn contains(&self, other: &PauseFlags) -> bool {
+        (self.deposit || !other.deposit)
+            && (self.withdraw || !other.withdraw)
+            && (self.open_ticket || !other.open_ticket)
+            && (self.rebalance || !other.rebalance)
+    }
+}
+
+/// Vault fees
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default, Debug)]
+pub struct FeeParams {
+    /// Fee taken over the generated rewards (in basis points)
+    pub performance_fee_bps: u16,
+    /// Annual fee taken over the TVL (in basis points)
+    pub management_fee_bps: u16,
+    /// Fee taken over the minted LPs when depositing (in basis points)
+    pub deposit_fee_bps: u16,
+    /// Fee taken over the burned LPs when withdrawing (in basis points)
+    pub withdraw_fee_bps: u16,
+}
+
+impl FeeParams {
+    pub const SIZE: usize = 2 + 2 + 2 + 2;
+
+    /// Check the fees are below their maximum values
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.performance_fee_bps <= MAX_PERFORMANCE_FEE_BPS,
+            ErrorCode::InvalidFee
+        );
+        require!(
+            self.management_fee_bps <= MAX_MANAGEMENT_FEE_BPS,
+            ErrorCode::InvalidFee
+        );
+        require!(
+            self.deposit_fee_bps <= MAX_DEPOSIT_FEE_BPS,
+            ErrorCode::InvalidFee
+        );
+        require!(
+            self.withdraw_fee_bps <= MAX_WITHDRAW_FEE_BPS,
+            ErrorCode::InvalidFee
+        );
+        Ok(())
+    }
+
+    /// Compute the LP amount taken as fee out of the LPs minted in a deposit
+    pub fn deposit_fee_lp(&self, lp_amount: u64) -> Result<u64> {
+        bps_of(lp_amount, self.deposit_fee_bps)
+    }
+
+    /// Compute the LP amount taken as fee out of the LPs burned in a withdrawal
+    pub fn withdraw_fee_lp(&self, lp_amount: u64) -> Result<u64> {
+        bps_of(lp_amount, self.withdraw_fee_bps)
+    }
+
+    /// Compute the LP amount taken as fee on top of the LPs burned in a withdrawal, rounding up
+    /// so that it is never lower than `withdraw_fee_lp` of the total LPs spent
+    pub fn withdraw_fee_lp_on_burned(&self, lp_amount_to_burn: u64) -> Result<u64> {
+        let fee = self.withdraw_fee_bps as u128;
+        let denominator = (FEES_SCALE as u128)
+            .checked_sub(fee)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let value = (lp_amount_to_burn as u128)
+            .checked_mul(fee)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_add(denominator - 1)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(denominator)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        Ok(value)
+    }
+
+    /// Compute the LP amount to be minted as management fee, such that the minted LPs are worth
+    /// the annual `management_fee_bps` of the TVL accrued during the elapsed time (up to a year)
+    pub fn management_fee_lp(&self, elapsed_time: i64, lp_supply: u64) -> Result<u64> {
+        let fee = self.management_fee_bps as u64;
+        if fee == 0 || elapsed_time <= 0 || lp_supply == 0 {
+            return Ok(0);
+        }
+
+        let elapsed_time = cmp::min(elapsed_time, SECONDS_PER_YEAR) as u64;
+
+        // accrued = fee * elapsed_time / year
+        // lp_fee = supply * accrued / (1 - accrued)
+        let accrued = U192::from(fee)
+            .checked_mul(U192::from(elapsed_time))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let numerator = U192::from(lp_supply)
+            .checked_mul(accrued)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let denominator = U192::from(FEES_SCALE)
+            .checked_mul(U192::from(SECONDS_PER_YEAR))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_sub(accrued)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let lp_fee = numerator
+            .checked_div(denominator)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        require!(lp_fee <= U192::from(u64::MAX), ErrorCode::MathOverflow);
+        Ok(lp_fee.as_u64())
+    }
+
+    /// Compute the LP amount to be minted as performance fee, such that the minted LPs are
+    /// worth `performance_fee_bps` of the rewards once these are accounted in the TVL
+    pub fn performance_fee_lp(
+        &self,
+        rewards: u64,
+        current_tvl: u64,
+        lp_supply: u64,
+    ) -> Result<u64> {
+        let fee = self.performance_fee_bps as u64;
+        if fee == 0 || rewards == 0 || lp_supply == 0 {
+            return Ok(0);
+        }
+
+        // lp_fee = fee * rewards * supply / (tvl + (1 - fee) * rewards)
+        let numerator = U192::from(fee)
+            .checked_mul(U192::from(rewards))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_mul(U192::from(lp_supply))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let denominator = U192::from(FEES_SCALE)
+            .checked_mul(U192::from(current_tvl))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_add(
+                U192::from(
+                    (FEES_SCALE as u64)
+                        .checked_sub(fee)
+                        .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
+                )
+                .checked_mul(U192::from(rewards))
+                .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
+            )
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        let lp_fee = numerator
+            .checked_div(denominator)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+
+        require!(lp_fee <= U192::from(u64::MAX), ErrorCode::MathOverflow);
+        Ok(lp_fee.as_u64())
+    }
+}
+
+/// Compute the given basis points of the amount
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+        .checked_div(FEES_SCALE as u128)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?;
+    Ok(value)
+}
+
+/// Compute the share of the amount given by the weight
+fn weighted_amount(amount: u64, weight: u32) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(weight as u128)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+        .checked_div(WEIGHTS_SCALE.into())
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?;
+    Ok(value)
+}
+
+/// Strategy refresh parameters
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct RefreshParams {
+    /// Minimum elapsed slots for updating the protocol weights
+    pub min_elapsed_time: i64,
+    /// Minimum amount of lamports to deposit in each protocol
+    pub min_deposit_lamports: u64,
+}
+
+impl RefreshParams {
+    pub const SIZE: usize = 8 + 8;
+}
+
+/// Unit of the minimum deviation from the protocol targets
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeviationUnit {
+    /// Basis points of the invested TVL
+    Bps,
+    /// Input token amount
+    Tokens,
+}
+
+impl Default for DeviationUnit {
+    fn default() -> Self {
+        DeviationUnit::Bps
+    }
+}
+
+/// Rebalance damping parameters
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct DampingParams {
+    /// Maximum change of a protocol weight on each refresh (zero if unlimited)
+    pub max_weight_delta: u16,
+    /// Minimum deviation of a protocol from its target for depositing or withdrawing the
+    /// difference (zero if none)
+    pub min_deviation: u64,
+    /// Unit of the minimum deviation
+    pub min_deviation_unit: DeviationUnit,
+}
+
+impl DampingParams {
+    pub const SIZE: usize = 2 + 8 + 1;
+
+    /// Check the damping parameters
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.max_weight_delta as u32 <= WEIGHTS_SCALE,
+            ErrorCode::InvalidDampingParams
+        );
+        require!(
+            self.min_deviation_unit == DeviationUnit::Tokens
+                || self.min_deviation <= WEIGHTS_SCALE as u64,
+            ErrorCode::InvalidDampingParams
+        );
+        Ok(())
+    }
+
+    /// Return the deviation from the target, or zero if below the minimum deviation
+    fn above_min_deviation(&self, deviation: u64, invested_tvl: u64) -> Result<u64> {
+        let min_deviation = match self.min_deviation_unit {
+            DeviationUnit::Bps => weighted_amount(invested_tvl, self.min_deviation as u32)?,
+            DeviationUnit::Tokens => self.min_deviation,
+        };
+
+        if deviation < min_deviation {
+            Ok(0)
+        } else {
+            Ok(deviation)
+        }
+    }
+}
+
+/// Algorithm updating the protocol weights
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WeightStrategy {
+    /// Move the weights towards the protocols with higher realized rewards per deposited unit
+    Auto,
+    /// Maximize the yield expected from the current supply rates of the protocol reserves
+    SupplyRate,
+    /// Keep the weights set by the strategist
+    Manual,
+    /// Spread the invested TVL equally across the active protocols
+    EqualWeight,
+    /// Move the invested TVL to the protocol with the highest realized rewards per deposited unit
+    MaxYield,
+    /// Spread the invested TVL in proportion to the realized rewards per deposited unit of each
+    /// protocol divided by its risk score
+    RiskAdjusted,
+}
+
+impl Default for WeightStrategy {
+    fn default() -> Self {
+        WeightStrategy::Auto
+    }
+}
+
+/// Protocol data
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct ProtocolData {
+    /// Protocol ID
+    pub protocol_id: u8,
+
+    /// Hashes of Pubkey
+    pub hash_pubkey: HashPubkey,
+
+    /// Percentage of the invested TVL (excluding the idle buffer) that should be deposited in the
+    /// protocol
+    pub weight: u32,
+    /// Deposited token amount in the protocol
+    pub amount: u64,
+    /// Accumulated rewards
+    pub rewards: AccumulatedRewards,
+
+    /// Maximum weight of the protocol (zero if uncapped)
+    pub max_weight: u32,
+    /// Maximum token amount deposited in the protocol (zero if uncapped)
+    pub max_amount: u64,
+
+    /// Last snapshot of the protocol reserve supply and interest rate curve
+    pub reserve_rates: ReserveRates,
+    /// Risk score dividing the protocol yield in the risk adjusted strategy (zero if not rated,
+    /// handled as the lowest score)
+    pub risk_score: u8,
 }
 
 impl ProtocolData {
-    pub const SIZE: usize = 1 + HashPubkey::SIZE + 4 + 8 + AccumulatedRewards::SIZE + 8 * 5;
+    pub const SIZE: usize =
+        1 + HashPubkey::SIZE + 4 + 8 + AccumulatedRewards::SIZE + 4 + 8 + ReserveRates::SIZE + 1;
 
     /// Check the protocol is active
     pub fn is_active(&self) -> bool {
@@ -330,16 +1267,83 @@ impl ProtocolData {
         self.hash_pubkey.hash_tvl = hashes[2];
     }
 
-    /// Amount that should be deposited according to the weight
-    fn amount_should_be_deposited(&self, total_amount: u64) -> Result<u64> {
-        let amount: u64 = (total_amount as u128)
-            .checked_mul(self.weight as u128)
-            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-            .checked_div(WEIGHTS_SCALE.into())
-            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+    /// Set the protocol risk score
+    pub fn set_risk_score(&mut self, risk_score: u8) {
+        self.risk_score = risk_score;
+    }
+
+    /// Check and set the protocol caps
+    pub fn set_caps(&mut self, max_weight: u32, max_amount: u64) -> Result<()> {
+        require!(max_weight <= WEIGHTS_SCALE, ErrorCode::InvalidWeights);
+        self.max_weight = max_weight;
+        self.max_amount = max_amount;
+        Ok(())
+    }
+
+    /// Maximum weight of the protocol given the invested TVL, considering both the weight and
+    /// the token amount caps. An active protocol is never capped below the minimum weight unit
+    fn weight_cap(&self, invested_tvl: u64) -> Result<u32> {
+        let mut cap = if self.max_weight == 0 {
+            WEIGHTS_SCALE
+        } else {
+            self.max_weight
+        };
+
+        if self.max_amount > 0 && invested_tvl > 0 {
+            let amount_cap: u32 = cmp::min(
+                (self.max_amount as u128)
+                    .checked_mul(WEIGHTS_SCALE.into())
+                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+                    .checked_div(invested_tvl.into())
+                    .ok_or_else(|| error!(ErrorCode::MathOverflow))?,
+                WEIGHTS_SCALE.into(),
+            )
             .try_into()
             .map_err(|_| ErrorCode::MathOverflow)?;
-        Ok(amount)
+            cap = cmp::min(cap, cmp::max(amount_cap, 1));
+        }
+
+        Ok(cap)
+    }
+
+    /// Amount that should be deposited in the protocol, limited by its weight and token amount caps
+    fn target_amount(&self, invested_tvl: u64) -> Result<u64> {
+        let weight = cmp::min(self.weight, self.weight_cap(invested_tvl)?);
+        let amount = weighted_amount(invested_tvl, weight)?;
+        if self.max_amount > 0 {
+            Ok(cmp::min(amount, self.max_amount))
+        } else {
+            Ok(amount)
+        }
+    }
+
+    /// Expected yield per weight unit of adding up to `step` to the protocol weight, or `None` if
+    /// the protocol is inactive or already at its cap
+    fn marginal_yield(
+        &self,
+        invested_tvl: u64,
+        weight: u32,
+        cap: u32,
+        step: u32,
+    ) -> Result<Option<u128>> {
+        if !self.is_active() || weight >= cap {
+            return Ok(None);
+        }
+
+        let added = cmp::min(step, cap - weight);
+        let amount_before = weighted_amount(invested_tvl, weight)?;
+        let amount_after = weighted_amount(invested_tvl, weight + added)?;
+
+        let yield_before = self
+            .reserve_rates
+            .expected_yield(self.amount, amount_before)?;
+        let yield_after = self
+            .reserve_rates
+            .expected_yield(self.amount, amount_after)?;
+
+        Ok(Some(
+            yield_after.saturating_sub(yield_before) / added as u128,
+        ))
     }
 
     /// Update the protocol tvl with the generated rewards
@@ -392,6 +1396,123 @@ impl HashPubkey {
     pub const SIZE: usize = CHECKHASH_BYTES * 3;
 }
 
+/// Snapshot of a protocol reserve, used to estimate its supply rate given the vault deposit
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct ReserveRates {
+    /// Total liquidity supplied to the reserve (available plus borrowed), including the vault
+    /// deposit
+    pub total_supply: u64,
+    /// Liquidity borrowed from the reserve
+    pub borrowed_amount: u64,
+    /// Utilization (in percent) of the inner points of the borrow rate curve
+    pub curve_utilization: [u8; 2],
+    /// Borrow rate (in basis points) at zero utilization, at the inner points of the curve and at
+    /// full utilization
+    pub curve_borrow_rate_bps: [u16; 4],
+    /// Percentage of the borrow interests taken by the protocol
+    pub take_rate: u8,
+}
+
+impl ReserveRates {
+    pub const SIZE: usize = 8 + 8 + 2 + 2 * 4 + 1;
+
+    /// Snapshot of a reserve with the usual two slopes borrow rate curve, increasing from the
+    /// minimum rate to the optimal rate at the optimal utilization, and then to the maximum rate
+    /// (rates and utilization in percent)
+    pub fn from_two_slopes_curve(
+        total_supply: u64,
+        borrowed_amount: u64,
+        optimal_utilization: u8,
+        min_borrow_rate: u8,
+        optimal_borrow_rate: u8,
+        max_borrow_rate: u8,
+    ) -> Self {
+        let optimal_utilization = cmp::min(optimal_utilization, 100);
+        Self {
+            total_supply,
+            borrowed_amount,
+            curve_utilization: [optimal_utilization; 2],
+            curve_borrow_rate_bps: [
+                min_borrow_rate as u16 * 100,
+                optimal_borrow_rate as u16 * 100,
+                optimal_borrow_rate as u16 * 100,
+                max_borrow_rate as u16 * 100,
+            ],
+            take_rate: 0,
+        }
+    }
+
+    /// Borrow rate (in basis points) at the given utilization (in basis points), interpolating
+    /// linearly between the points of the curve
+    pub fn borrow_rate_bps(&self, utilization_bps: u64) -> u64 {
+        let utilization_bps = cmp::min(utilization_bps, FEES_SCALE as u64);
+        let points = [
+            (0, self.curve_borrow_rate_bps[0]),
+            (self.curve_utilization[0], self.curve_borrow_rate_bps[1]),
+            (self.curve_utilization[1], self.curve_borrow_rate_bps[2]),
+            (100, self.curve_borrow_rate_bps[3]),
+        ];
+
+        for segment in points.windows(2) {
+            let (start, start_rate) = (segment[0].0 as i64 * 100, segment[0].1 as i64);
+            let (end, end_rate) = (segment[1].0 as i64 * 100, segment[1].1 as i64);
+            if (utilization_bps as i64) <= end && end > start {
+                let rate = start_rate
+                    + (end_rate - start_rate) * (utilization_bps as i64 - start) / (end - start);
+                return cmp::max(rate, 0) as u64;
+            }
+        }
+
+        self.curve_borrow_rate_bps[3] as u64
+    }
+
+    /// Utilization (in basis points) of the reserve if the vault deposit changed from `deposited`
+    /// to `amount`
+    fn utilization_bps(&self, deposited: u64, amount: u64) -> Result<u64> {
+        let total_supply = (self.total_supply.saturating_sub(deposited) as u128)
+            .checked_add(amount.into())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        if total_supply == 0 {
+            return Ok(0);
+        }
+
+        let utilization = (self.borrowed_amount as u128)
+            .checked_mul(FEES_SCALE.into())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(total_supply)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        Ok(cmp::min(utilization, FEES_SCALE.into()) as u64)
+    }
+
+    /// Supply rate (in basis points) of the reserve if the vault deposit changed from `deposited`
+    /// to `amount`
+    pub fn supply_rate_bps(&self, deposited: u64, amount: u64) -> Result<u64> {
+        let utilization_bps = self.utilization_bps(deposited, amount)?;
+        let rate = self
+            .borrow_rate_bps(utilization_bps)
+            .checked_mul(utilization_bps)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_mul(100_u64.saturating_sub(self.take_rate.into()))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(FEES_SCALE as u64 * 100)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        Ok(rate)
+    }
+
+    /// Yearly yield (scaled by 10^10) of depositing `amount` in the reserve, where the vault
+    /// deposit included in the snapshot is `deposited`
+    pub fn expected_yield(&self, deposited: u64, amount: u64) -> Result<u128> {
+        let utilization_bps = self.utilization_bps(deposited, amount)?;
+        (amount as u128)
+            .checked_mul(self.borrow_rate_bps(utilization_bps).into())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_mul(utilization_bps.into())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_mul(100_u128.saturating_sub(self.take_rate.into()))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))
+    }
+}
+
 /// Generated rewards
 #[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
 pub struct AccumulatedRewards {
@@ -408,6 +1529,23 @@ pub struct AccumulatedRewards {
 impl AccumulatedRewards {
     pub const SIZE: usize = 8 + 8 + 16 + SlotIntegrated::SIZE;
 
+    /// Rewards generated per deposited unit (scaled by WAD), zero for a loss or without deposit
+    pub fn rewards_per_deposit_wad(&self) -> Result<u128> {
+        if self.amount <= 0 || self.deposited_avg_wad == 0 {
+            return Ok(0);
+        }
+
+        let value = U192::from(self.amount as u64)
+            .checked_mul(U192::from(WAD))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_mul(U192::from(WAD))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(U192::from(self.deposited_avg_wad))
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?;
+        require!(value <= U192::from(u128::MAX), ErrorCode::MathOverflow);
+        Ok(value.as_u128())
+    }
+
     /// Update the rewards
     pub fn update(&mut self, rewards: i64, deposited_amount: u64) -> Result<()> {
         let current_slot = Clock::get()?.slot;
@@ -513,43 +1651,61 @@ impl LpPrice {
 
     /// Transform input token amount to LP amount
     pub fn token_to_lp(&self, amount: u64) -> Result<u64> {
-        if self.minted_tokens == 0 {
-            Ok(amount)
-        } else {
-            Ok((amount as u128)
-                .checked_mul(self.minted_tokens as u128)
-                .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                .checked_div(self.total_tokens as u128)
-                .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                .try_into()
-                .map_err(|_| ErrorCode::MathOverflow)?)
-        }
+        Ok((amount as u128)
+            .checked_mul(self.virtual_minted_tokens())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(self.virtual_total_tokens())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?)
+    }
+
+    /// Transform input token amount to LP amount, rounding up
+    pub fn token_to_lp_round_up(&self, amount: u64) -> Result<u64> {
+        let total_tokens = self.virtual_total_tokens();
+        Ok((amount as u128)
+            .checked_mul(self.virtual_minted_tokens())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_add(total_tokens - 1)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(total_tokens)
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?)
     }
 
     /// Transform LP amount to input token amount
     pub fn lp_to_token(&self, lp_amount: u64) -> Result<u64> {
-        if self.minted_tokens == 0 {
-            Ok(lp_amount)
-        } else {
-            Ok((lp_amount as u128)
-                .checked_mul(self.total_tokens as u128)
-                .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                .checked_div(self.minted_tokens as u128)
-                .ok_or_else(|| error!(ErrorCode::MathOverflow))?
-                .try_into()
-                .map_err(|_| ErrorCode::MathOverflow)?)
-        }
+        Ok((lp_amount as u128)
+            .checked_mul(self.virtual_total_tokens())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .checked_div(self.virtual_minted_tokens())
+            .ok_or_else(|| error!(ErrorCode::MathOverflow))?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?)
+    }
+
+    /// Total tokens including the virtual offset
+    fn virtual_total_tokens(&self) -> u128 {
+        self.total_tokens as u128 + VIRTUAL_LP_OFFSET as u128
+    }
+
+    /// Minted LPs including the virtual offset
+    fn virtual_minted_tokens(&self) -> u128 {
+        self.minted_tokens as u128 + VIRTUAL_LP_OFFSET as u128
     }
 }
 
 impl PartialEq for LpPrice {
     fn eq(&self, other: &Self) -> bool {
-        let lhs = (self.total_tokens as u128)
-            .checked_mul(other.minted_tokens as u128)
+        let lhs = self
+            .virtual_total_tokens()
+            .checked_mul(other.virtual_minted_tokens())
             .unwrap();
 
-        let rhs = (other.total_tokens as u128)
-            .checked_mul(self.minted_tokens as u128)
+        let rhs = other
+            .virtual_total_tokens()
+            .checked_mul(self.virtual_minted_tokens())
             .unwrap();
 
         lhs == rhs
@@ -558,12 +1714,14 @@ impl PartialEq for LpPrice {
 
 impl PartialOrd for LpPrice {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let lhs = (self.total_tokens as u128)
-            .checked_mul(other.minted_tokens as u128)
+        let lhs = self
+            .virtual_total_tokens()
+            .checked_mul(other.virtual_minted_tokens())
             .unwrap();
 
-        let rhs = (other.total_tokens as u128)
-            .checked_mul(self.minted_tokens as u128)
+        let rhs = other
+            .virtual_total_tokens()
+            .checked_mul(self.virtual_minted_tokens())
             .unwrap();
 
         lhs.partial_cmp(&rhs)
@@ -594,4 +1752,1155 @@ mod test {
         assert_eq!(price, same_price);
         assert!(greater_price > price);
     }
+
+    #[test]
+    fn test_lp_price_cmp_virtual() {
+        // Prices are compared the same way they are used, including the virtual offset
+        let donated_price = LpPrice {
+            minted_tokens: 0,
+            total_tokens: 1_000,
+        };
+        assert!(donated_price > LpPrice::default());
+        assert_ne!(donated_price, LpPrice::default());
+
+        let price = LpPrice {
+            minted_tokens: 1,
+            total_tokens: 2,
+        };
+        let greater_price = LpPrice {
+            minted_tokens: 1_000_000,
+            total_tokens: 1_500_000,
+        };
+        assert!(
+            price.lp_to_token(1_000_000).unwrap() < greater_price.lp_to_token(1_000_000).unwrap()
+        );
+        assert!(price < greater_price);
+    }
+
+    #[test]
+    fn test_performance_fee_validate() {
+        let fees = FeeParams {
+            performance_fee_bps: MAX_PERFORMANCE_FEE_BPS,
+            management_fee_bps: MAX_MANAGEMENT_FEE_BPS,
+            deposit_fee_bps: MAX_DEPOSIT_FEE_BPS,
+            withdraw_fee_bps: MAX_WITHDRAW_FEE_BPS,
+        };
+        assert!(fees.validate().is_ok());
+
+        let fees = FeeParams {
+            performance_fee_bps: MAX_PERFORMANCE_FEE_BPS + 1,
+            ..FeeParams::default()
+        };
+        assert!(fees.validate().is_err());
+
+        let fees = FeeParams {
+            management_fee_bps: MAX_MANAGEMENT_FEE_BPS + 1,
+            ..FeeParams::default()
+        };
+        assert!(fees.validate().is_err());
+
+        let fees = FeeParams {
+            deposit_fee_bps: MAX_DEPOSIT_FEE_BPS + 1,
+            ..FeeParams::default()
+        };
+        assert!(fees.validate().is_err());
+
+        let fees = FeeParams {
+            withdraw_fee_bps: MAX_WITHDRAW_FEE_BPS + 1,
+            ..FeeParams::default()
+        };
+        assert!(fees.validate().is_err());
+    }
+
+    #[test]
+    fn test_vault_init_seed_number() {
+        let vault = VaultAccount::init(InitVaultAccountParams {
+            seed_number: 3,
+            bumps: Bumps {
+                vault: 254,
+                lp_token_mint: 253,
+            },
+            input_mint_pubkey: Pubkey::new_unique(),
+            dao_treasury_lp_token_account: Pubkey::new_unique(),
+        });
+
+        assert_eq!(vault.seed_number, 3);
+        assert_eq!(vault.bumps.vault, 254);
+        assert_eq!(vault.bumps.lp_token_mint, 253);
+    }
+
+    #[test]
+    fn test_reconcile_holdings() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000,
+            protocols: vec![
+                ProtocolData {
+                    amount: 600,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    amount: 300,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+
+        // Balanced holdings
+        assert_eq!(vault.reconcile_holdings(100).unwrap(), 0);
+        assert_eq!(vault.rewards_sum, 0);
+        assert_eq!(vault.pending_loss, 0);
+
+        // A donation is booked as rewards once
+        assert_eq!(vault.reconcile_holdings(150).unwrap(), 50);
+        assert_eq!(vault.rewards_sum, 50);
+        assert_eq!(vault.reconcile_holdings(150).unwrap(), 0);
+        assert_eq!(vault.rewards_sum, 50);
+
+        // A shortfall is taken from the pending rewards first
+        vault.protocols[0].amount = 500;
+        assert_eq!(vault.reconcile_holdings(150).unwrap(), -100);
+        assert_eq!(vault.rewards_sum, 0);
+        assert_eq!(vault.pending_loss, 50);
+        assert_eq!(vault.reconcile_holdings(150).unwrap(), 0);
+        assert_eq!(vault.pending_loss, 50);
+
+        // A surplus recovers the pending loss first
+        assert_eq!(vault.reconcile_holdings(230).unwrap(), 80);
+        assert_eq!(vault.pending_loss, 0);
+        assert_eq!(vault.rewards_sum, 30);
+
+        // Funds reserved for the withdrawal tickets are not part of the holdings
+        vault.reserved_for_tickets = 70;
+        assert_eq!(vault.reconcile_holdings(300).unwrap(), 0);
+        assert_eq!(vault.available_idle(60), 0);
+    }
+
+    #[test]
+    fn test_accumulate_protocol_rewards() {
+        let mut vault = VaultAccount {
+            rewards_sum: 10,
+            protocols: vec![ProtocolData::default(), ProtocolData::default()],
+            ..VaultAccount::default()
+        };
+
+        vault.protocols[0].rewards.amount = 30;
+        vault.protocols[1].rewards.amount = -5;
+        vault.accumulate_protocol_rewards().unwrap();
+        assert_eq!(vault.rewards_sum, 35);
+        assert_eq!(vault.pending_loss, 0);
+
+        // A net loss is not booked as negative rewards
+        vault.protocols[0].rewards.amount = -50;
+        vault.protocols[1].rewards.amount = 0;
+        vault.accumulate_protocol_rewards().unwrap();
+        assert_eq!(vault.rewards_sum, 0);
+        assert_eq!(vault.pending_loss, 15);
+    }
+
+    #[test]
+    fn test_recognize_loss() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000,
+            pending_loss: 100,
+            ..VaultAccount::default()
+        };
+
+        assert!(vault.recognize_loss(0).is_err());
+        assert!(vault.recognize_loss(101).is_err());
+
+        vault.recognize_loss(40).unwrap();
+        assert_eq!(vault.current_tvl, 960);
+        assert_eq!(vault.pending_loss, 60);
+
+        vault.recognize_loss(60).unwrap();
+        assert_eq!(vault.current_tvl, 900);
+        assert_eq!(vault.pending_loss, 0);
+    }
+
+    #[test]
+    fn test_withdraw_lp_price() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000_000,
+            previous_lp_price: LpPrice {
+                total_tokens: 1_000_000,
+                minted_tokens: 500_000,
+            },
+            protocols: vec![ProtocolData::default()],
+            ..VaultAccount::default()
+        };
+        assert_eq!(
+            vault
+                .withdraw_lp_price()
+                .unwrap()
+                .lp_to_token(500_000)
+                .unwrap(),
+            vault.previous_lp_price.lp_to_token(500_000).unwrap()
+        );
+
+        // A 10% loss is pending
+        vault.rewards_sum = 1_000;
+        vault.protocols[0].rewards.amount = -101_000;
+        vault.accumulate_protocol_rewards().unwrap();
+        assert_eq!(vault.pending_loss, 100_000);
+
+        let price = vault.withdraw_lp_price().unwrap();
+        assert_eq!(price.total_tokens, 900_000);
+        assert_eq!(price.minted_tokens, 500_000);
+
+        // Recognizing the loss does not change the withdrawal price
+        vault.recognize_loss(100_000).unwrap();
+        vault.previous_lp_price = LpPrice {
+            total_tokens: vault.current_tvl,
+            minted_tokens: 500_000,
+        };
+        assert_eq!(vault.withdraw_lp_price().unwrap().total_tokens, 900_000);
+    }
+
+    #[test]
+    fn test_deposit_lp_price() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000_000,
+            previous_lp_price: LpPrice {
+                total_tokens: 1_000_000,
+                minted_tokens: 500_000,
+            },
+            protocols: vec![ProtocolData::default()],
+            ..VaultAccount::default()
+        };
+        let price = vault.deposit_lp_price(500_000);
+        assert_eq!(price.total_tokens, 1_000_000);
+        assert!(price >= vault.previous_lp_price);
+
+        // A 10% loss is pending
+        vault.rewards_sum = 1_000;
+        vault.protocols[0].rewards.amount = -101_000;
+        vault.accumulate_protocol_rewards().unwrap();
+        assert_eq!(vault.current_tvl, 1_000_000);
+
+        // The deposits are priced without the loss, below the previous price
+        let price = vault.deposit_lp_price(500_000);
+        assert_eq!(price.total_tokens, 900_000);
+        assert!(price < vault.previous_lp_price);
+        assert!(
+            price.token_to_lp(9_000).unwrap() > vault.previous_lp_price.token_to_lp(9_000).unwrap()
+        );
+
+        // Recognizing the loss does not change the deposit price
+        vault.recognize_loss(100_000).unwrap();
+        assert_eq!(vault.deposit_lp_price(500_000).total_tokens, 900_000);
+    }
+
+    #[test]
+    fn test_rebalance_amounts() {
+        let vault = VaultAccount {
+            current_tvl: 1_000,
+            protocols: vec![
+                ProtocolData {
+                    weight: 5_000,
+                    amount: 700,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 3_000,
+                    amount: 100,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 2_000,
+                    amount: 200,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+
+        assert_eq!(vault.deposit_excess(0).unwrap(), 200);
+        assert_eq!(vault.deposit_shortfall(0).unwrap(), 0);
+        assert_eq!(vault.calculate_withdraw(0).unwrap(), 200);
+        assert!(vault.calculate_deposit(0, 1_000).is_err());
+
+        assert_eq!(vault.deposit_excess(1).unwrap(), 0);
+        assert_eq!(vault.deposit_shortfall(1).unwrap(), 200);
+        assert_eq!(vault.calculate_deposit(1, 150).unwrap(), 150);
+        assert!(vault.calculate_withdraw(1).is_err());
+
+        // Protocols on target are neither deposited nor withdrawn
+        assert_eq!(vault.deposit_excess(2).unwrap(), 0);
+        assert_eq!(vault.deposit_shortfall(2).unwrap(), 0);
+        assert!(vault.calculate_deposit(2, 1_000).is_err());
+        assert!(vault.calculate_withdraw(2).is_err());
+    }
+
+    #[test]
+    fn test_deposit_withdraw_fee_lp() {
+        let fees = FeeParams {
+            deposit_fee_bps: 10,
+            withdraw_fee_bps: 50,
+            ..FeeParams::default()
+        };
+
+        assert_eq!(fees.deposit_fee_lp(1_000_000).unwrap(), 1_000);
+        assert_eq!(fees.withdraw_fee_lp(1_000_000).unwrap(), 5_000);
+
+        // Rounded down in favour of the user
+        assert_eq!(fees.deposit_fee_lp(999).unwrap(), 0);
+        assert_eq!(fees.withdraw_fee_lp(u64::MAX).unwrap(), u64::MAX / 200);
+
+        assert_eq!(FeeParams::default().deposit_fee_lp(1_000_000).unwrap(), 0);
+        assert_eq!(FeeParams::default().withdraw_fee_lp(1_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_withdraw_fee_lp_on_burned() {
+        let fees = FeeParams {
+            withdraw_fee_bps: 50,
+            ..FeeParams::default()
+        };
+
+        // 995_000 burned out of 1_000_000 spent
+        assert_eq!(fees.withdraw_fee_lp_on_burned(995_000).unwrap(), 5_000);
+
+        // Rounded up in favour of the vault
+        for lp_amount_to_burn in [1, 199, 1_000_001, 123_456_789] {
+            let lp_fee = fees.withdraw_fee_lp_on_burned(lp_amount_to_burn).unwrap();
+            assert!(lp_fee > 0);
+            assert!(lp_fee >= fees.withdraw_fee_lp(lp_amount_to_burn + lp_fee).unwrap());
+        }
+
+        assert_eq!(
+            FeeParams::default()
+                .withdraw_fee_lp_on_burned(1_000_000)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_token_to_lp_round_up() {
+        // 3 tokens per LP, accounting for the virtual offset
+        let price = LpPrice {
+            total_tokens: 3 * 1_000_000 + 2 * VIRTUAL_LP_OFFSET,
+            minted_tokens: 1_000_000,
+        };
+
+        assert_eq!(price.token_to_lp_round_up(3_000).unwrap(), 1_000);
+        assert_eq!(price.token_to_lp_round_up(3_001).unwrap(), 1_001);
+        assert_eq!(price.token_to_lp(3_001).unwrap(), 1_000);
+        assert_eq!(price.token_to_lp_round_up(1).unwrap(), 1);
+        assert_eq!(price.token_to_lp_round_up(0).unwrap(), 0);
+
+        // Burning the rounded up LPs always covers the requested amount
+        for amount in [1, 2, 999, 1_234] {
+            let lp_amount = price.token_to_lp_round_up(amount).unwrap();
+            assert!(price.lp_to_token(lp_amount).unwrap() >= amount);
+        }
+
+        assert_eq!(
+            LpPrice::default().token_to_lp_round_up(1_000).unwrap(),
+            1_000
+        );
+    }
+
+    #[test]
+    fn test_first_deposit_lp_price() {
+        // The first deposit is minted 1:1
+        let price = LpPrice::default();
+        assert_eq!(price.token_to_lp(1_000_000_000).unwrap(), 1_000_000_000);
+        assert_eq!(price.lp_to_token(1_000_000_000).unwrap(), 1_000_000_000);
+    }
+
+    #[test]
+    fn test_first_depositor_inflation_attack() {
+        let victim_amount = 1_000_000_000;
+
+        // The attacker mints a single LP and donates the victim deposit amount to inflate the TVL
+        let attacker_lp = LpPrice::default().token_to_lp(1).unwrap();
+        assert_eq!(attacker_lp, 1);
+        let donation = victim_amount;
+        let mut price = LpPrice {
+            total_tokens: 1 + donation,
+            minted_tokens: attacker_lp,
+        };
+
+        // The victim deposit is not rounded down to zero LPs
+        let victim_lp = price.token_to_lp(victim_amount).unwrap();
+        assert!(victim_lp > 0);
+        price.total_tokens += victim_amount;
+        price.minted_tokens += victim_lp;
+
+        // The victim keeps almost all the deposited value
+        let victim_value = price.lp_to_token(victim_lp).unwrap();
+        assert!(victim_value >= victim_amount - victim_amount / 1_000);
+
+        // The attacker loses almost all the donation
+        let attacker_value = price.lp_to_token(attacker_lp).unwrap();
+        assert!(attacker_value < 1 + donation / 1_000);
+    }
+
+    #[test]
+    fn test_first_depositor_inflation_attack_large_donation() {
+        let victim_amount = 1_000_000;
+
+        // Rounding the victim down to zero LPs requires donating the victim amount times the
+        // virtual offset
+        let donation = victim_amount * VIRTUAL_LP_OFFSET;
+        let mut price = LpPrice {
+            total_tokens: 1 + donation,
+            minted_tokens: 1,
+        };
+
+        let victim_lp = price.token_to_lp(victim_amount).unwrap();
+        assert_eq!(victim_lp, 0);
+        price.total_tokens += victim_amount;
+
+        // The attacker does not recover the donation
+        let attacker_value = price.lp_to_token(1).unwrap();
+        assert!(attacker_value < donation / 1_000);
+    }
+
+    #[test]
+    fn test_performance_fee_lp() {
+        let fees = FeeParams {
+            performance_fee_bps: 1_000,
+            ..FeeParams::default()
+        };
+
+        let current_tvl = 1_000_000_000_000;
+        let lp_supply = 1_000_000_000_000;
+        let rewards = 100_000_000_000;
+
+        let lp_fee = fees
+            .performance_fee_lp(rewards, current_tvl, lp_supply)
+            .unwrap();
+        assert_eq!(lp_fee, 9_174_311_926);
+
+        // The minted LPs are worth the 10% of the rewards (minus the virtual LPs share)
+        let price = LpPrice {
+            total_tokens: current_tvl + rewards,
+            minted_tokens: lp_supply + lp_fee,
+        };
+        assert_eq!(price.lp_to_token(lp_fee).unwrap(), 9_999_999_181);
+
+        let no_fees = FeeParams::default();
+        assert_eq!(
+            no_fees
+                .performance_fee_lp(rewards, current_tvl, lp_supply)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            fees.performance_fee_lp(0, current_tvl, lp_supply).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_performance_fee_lp_zero_supply() {
+        let fees = FeeParams {
+            performance_fee_bps: MAX_PERFORMANCE_FEE_BPS,
+            ..FeeParams::default()
+        };
+
+        assert_eq!(fees.performance_fee_lp(100_000, 0, 0).unwrap(), 0);
+        assert_eq!(fees.performance_fee_lp(100_000, 1_000_000, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_performance_fee_lp_large_rewards() {
+        let fees = FeeParams {
+            performance_fee_bps: MAX_PERFORMANCE_FEE_BPS,
+            ..FeeParams::default()
+        };
+
+        let lp_supply = u64::MAX / 2;
+        let lp_fee = fees
+            .performance_fee_lp(u64::MAX / 2, u64::MAX / 2, lp_supply)
+            .unwrap();
+        assert!(lp_fee > 0 && lp_fee < lp_supply);
+
+        // All the TVL comes from rewards
+        let lp_fee = fees.performance_fee_lp(u64::MAX, 0, lp_supply).unwrap();
+        assert_eq!(
+            lp_fee as u128,
+            (MAX_PERFORMANCE_FEE_BPS as u128) * (lp_supply as u128)
+                / ((FEES_SCALE - MAX_PERFORMANCE_FEE_BPS) as u128)
+        );
+    }
+
+    #[test]
+    fn test_management_fee_lp() {
+        let fees = FeeParams {
+            management_fee_bps: 200,
+            ..FeeParams::default()
+        };
+
+        let current_tvl = 1_000_000_000_000;
+        let lp_supply = 1_000_000_000_000;
+
+        let lp_fee = fees.management_fee_lp(SECONDS_PER_YEAR, lp_supply).unwrap();
+        assert_eq!(lp_fee, 20_408_163_265);
+
+        // The minted LPs are worth the 2% of the TVL (plus the virtual LPs share)
+        let price = LpPrice {
+            total_tokens: current_tvl,
+            minted_tokens: lp_supply + lp_fee,
+        };
+        assert_eq!(price.lp_to_token(lp_fee).unwrap(), 20_000_000_399);
+
+        // Half a year accrues roughly half the fee
+        let lp_fee_half = fees
+            .management_fee_lp(SECONDS_PER_YEAR / 2, lp_supply)
+            .unwrap();
+        assert_eq!(lp_fee_half, 10_101_010_101);
+
+        // The accrual is capped to one year
+        assert_eq!(
+            fees.management_fee_lp(10 * SECONDS_PER_YEAR, lp_supply)
+                .unwrap(),
+            lp_fee
+        );
+    }
+
+    #[test]
+    fn test_management_fee_lp_zero() {
+        let fees = FeeParams {
+            management_fee_bps: MAX_MANAGEMENT_FEE_BPS,
+            ..FeeParams::default()
+        };
+
+        assert_eq!(fees.management_fee_lp(0, 1_000_000).unwrap(), 0);
+        assert_eq!(fees.management_fee_lp(-1, 1_000_000).unwrap(), 0);
+        assert_eq!(fees.management_fee_lp(SECONDS_PER_YEAR, 0).unwrap(), 0);
+        assert_eq!(
+            FeeParams::default()
+                .management_fee_lp(SECONDS_PER_YEAR, 1_000_000)
+                .unwrap(),
+            0
+        );
+        assert!(fees
+            .management_fee_lp(SECONDS_PER_YEAR, u64::MAX / 2)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_settle_bounty() {
+        let mut vault = VaultAccount::default();
+        assert_eq!(vault.settle_bounty(1_000_000).unwrap(), 0);
+
+        assert!(vault.set_settle_bounty(MAX_SETTLE_BOUNTY_BPS + 1).is_err());
+        vault.set_settle_bounty(10).unwrap();
+        assert_eq!(vault.settle_bounty(1_000_000).unwrap(), 1_000);
+        assert_eq!(vault.settle_bounty(999).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_idle_buffer() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000,
+            protocols: vec![
+                ProtocolData {
+                    weight: 5_000,
+                    amount: 500,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 5_000,
+                    amount: 300,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+
+        assert!(vault.set_idle_weight(WEIGHTS_SCALE + 1).is_err());
+        vault.set_idle_weight(2_000).unwrap();
+        assert_eq!(vault.idle_buffer().unwrap(), 200);
+        assert_eq!(vault.invested_tvl().unwrap(), 800);
+
+        // The protocol targets are computed over the invested TVL
+        assert_eq!(vault.deposit_excess(0).unwrap(), 100);
+        assert_eq!(vault.deposit_shortfall(1).unwrap(), 100);
+
+        // Everything is kept idle with the maximum idle weight
+        vault.set_idle_weight(WEIGHTS_SCALE).unwrap();
+        assert_eq!(vault.invested_tvl().unwrap(), 0);
+        assert_eq!(vault.deposit_excess(0).unwrap(), 500);
+        assert_eq!(vault.deposit_shortfall(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_deposit_keeps_idle_buffer() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000,
+            protocols: vec![
+                ProtocolData {
+                    weight: 5_000,
+                    amount: 500,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 5_000,
+                    amount: 300,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+        vault.set_idle_weight(2_000).unwrap();
+
+        // The first protocol holds the funds missing in the second one, so the idle funds are
+        // only the buffer
+        assert_eq!(vault.deposit_excess(0).unwrap(), 100);
+        assert_eq!(vault.deposit_shortfall(1).unwrap(), 100);
+        assert_eq!(vault.calculate_deposit(1, 200).unwrap(), 0);
+
+        // Only the idle funds above the buffer are deposited
+        assert_eq!(vault.calculate_deposit(1, 250).unwrap(), 50);
+        assert_eq!(vault.calculate_deposit(1, 1_000).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_cap_protocol_weights() {
+        let mut vault = VaultAccount {
+            current_tvl: 10_000,
+            protocols: vec![
+                ProtocolData {
+                    weight: 7_000,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 2_000,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 1_000,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+
+        // Uncapped protocols keep their weights
+        vault.cap_protocol_weights().unwrap();
+        let weights: Vec<u32> = vault.protocols.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, vec![7_000, 2_000, 1_000]);
+
+        // The excess is moved to the protocols below their caps
+        assert!(vault.protocols[0].set_caps(WEIGHTS_SCALE + 1, 0).is_err());
+        vault.protocols[0].set_caps(4_000, 0).unwrap();
+        vault.protocols[1].set_caps(0, 3_000).unwrap();
+        vault.cap_protocol_weights().unwrap();
+        let weights: Vec<u32> = vault.protocols.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, vec![4_000, 3_000, 3_000]);
+
+        // The excess that does not fit anywhere is kept idle
+        vault.protocols[2].set_caps(2_000, 0).unwrap();
+        vault.protocols[0].weight = 7_000;
+        vault.protocols[1].weight = 2_000;
+        vault.protocols[2].weight = 1_000;
+        vault.cap_protocol_weights().unwrap();
+        let weights: Vec<u32> = vault.protocols.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, vec![4_000, 3_000, 2_000]);
+    }
+
+    #[test]
+    fn test_refresh_protocol_weights_caps() {
+        let mut vault = VaultAccount {
+            current_tvl: 10_000,
+            protocols: vec![
+                ProtocolData {
+                    weight: 7_000,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 3_000,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+        vault.protocols[0].set_caps(4_000, 0).unwrap();
+
+        // A weight above a lowered cap is never the deposit target
+        assert_eq!(vault.deposit_shortfall(0).unwrap(), 4_000);
+
+        // The caps apply even without rewards to update the weights from
+        vault.refresh_protocol_weights().unwrap();
+        let weights: Vec<u32> = vault.protocols.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, vec![4_000, 6_000]);
+    }
+
+    #[test]
+    fn test_protocol_amount_cap() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000,
+            protocols: vec![ProtocolData {
+                weight: WEIGHTS_SCALE,
+                amount: 300,
+                ..ProtocolData::default()
+            }],
+            ..VaultAccount::default()
+        };
+        assert_eq!(vault.deposit_shortfall(0).unwrap(), 700);
+
+        vault.protocols[0].set_caps(0, 500).unwrap();
+        assert_eq!(vault.deposit_shortfall(0).unwrap(), 200);
+        assert_eq!(vault.calculate_deposit(0, 1_000).unwrap(), 200);
+
+        vault.protocols[0].amount = 800;
+        assert_eq!(vault.deposit_excess(0).unwrap(), 300);
+        assert_eq!(vault.calculate_withdraw(0).unwrap(), 300);
+
+        // An active protocol is never capped to a zero weight
+        vault.current_tvl = u64::MAX;
+        vault.protocols[0].set_caps(0, 1).unwrap();
+        vault.cap_protocol_weights().unwrap();
+        assert_eq!(vault.protocols[0].weight, 1);
+    }
+
+    #[test]
+    fn test_max_reserve_deposit() {
+        let mut vault = VaultAccount {
+            protocols: vec![ProtocolData {
+                amount: 100,
+                ..ProtocolData::default()
+            }],
+            ..VaultAccount::default()
+        };
+        assert_eq!(vault.max_reserve_deposit(0, 1_000).unwrap(), u64::MAX);
+
+        assert!(vault.set_max_reserve_share(FEES_SCALE + 1).is_err());
+        vault.set_max_reserve_share(2_000).unwrap();
+        // Owning 100 + 125 out of 1_000 + 125 is exactly 20%
+        assert_eq!(vault.max_reserve_deposit(0, 1_000).unwrap(), 125);
+        assert_eq!(vault.max_reserve_deposit(0, 500).unwrap(), 0);
+        assert_eq!(vault.max_reserve_deposit(0, 300).unwrap(), 0);
+
+        vault.set_max_reserve_share(FEES_SCALE).unwrap();
+        assert_eq!(vault.max_reserve_deposit(0, 300).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn test_reserve_rates_borrow_rate() {
+        let rates = ReserveRates::from_two_slopes_curve(0, 0, 80, 0, 10, 100);
+        assert_eq!(rates.borrow_rate_bps(0), 0);
+        assert_eq!(rates.borrow_rate_bps(4_000), 500);
+        assert_eq!(rates.borrow_rate_bps(8_000), 1_000);
+        assert_eq!(rates.borrow_rate_bps(9_000), 5_500);
+        assert_eq!(rates.borrow_rate_bps(10_000), 10_000);
+        assert_eq!(rates.borrow_rate_bps(20_000), 10_000);
+
+        let rates = ReserveRates {
+            curve_utilization: [50, 80],
+            curve_borrow_rate_bps: [100, 600, 1_000, 5_000],
+            ..ReserveRates::default()
+        };
+        assert_eq!(rates.borrow_rate_bps(2_500), 350);
+        assert_eq!(rates.borrow_rate_bps(6_500), 800);
+        assert_eq!(rates.borrow_rate_bps(9_000), 3_000);
+    }
+
+    #[test]
+    fn test_reserve_rates_supply_rate() {
+        let mut rates = ReserveRates::from_two_slopes_curve(1_000, 800, 80, 0, 10, 100);
+        assert_eq!(rates.supply_rate_bps(0, 0).unwrap(), 800);
+        // Depositing 600 more lowers the utilization to 50%
+        assert_eq!(rates.supply_rate_bps(0, 600).unwrap(), 312);
+        // Withdrawing 200 out of the 1_000 deposited raises the utilization to 100%
+        assert_eq!(rates.supply_rate_bps(1_000, 800).unwrap(), 10_000);
+        assert_eq!(
+            rates.expected_yield(0, 600).unwrap(),
+            600 * 625 * 5_000 * 100
+        );
+
+        rates.take_rate = 20;
+        assert_eq!(rates.supply_rate_bps(0, 0).unwrap(), 640);
+
+        assert_eq!(
+            ReserveRates::default().supply_rate_bps(0, 1_000).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_update_protocol_weights_from_supply_rates() {
+        let mut vault = VaultAccount {
+            current_tvl: 1_000,
+            weight_strategy: WeightStrategy::SupplyRate,
+            protocols: vec![
+                ProtocolData {
+                    weight: 5_000,
+                    reserve_rates: ReserveRates::from_two_slopes_curve(1_000, 800, 80, 0, 10, 100),
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 5_000,
+                    reserve_rates: ReserveRates::from_two_slopes_curve(
+                        4_000, 2_400, 80, 0, 10, 100,
+                    ),
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 0,
+                    reserve_rates: ReserveRates::from_two_slopes_curve(
+                        1_000, 1_000, 80, 0, 10, 100,
+                    ),
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 100,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+        vault.refresh_protocol_weights().unwrap();
+
+        // The marginal supply rates of both reserves end up close, the inactive protocol is not
+        // allocated and the protocol without snapshot only keeps the minimum weight
+        let weights: Vec<u32> = vault.protocols.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, vec![3_310, 6_680, 0, 10]);
+        assert_eq!(weights.iter().sum::<u32>(), WEIGHTS_SCALE);
+
+        // The weight above the caps goes to the next best protocol or is kept idle
+        vault.protocols[1].set_caps(5_000, 0).unwrap();
+        vault.refresh_protocol_weights().unwrap();
+        let weights: Vec<u32> = vault.protocols.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, vec![4_990, 5_000, 0, 10]);
+
+        vault.protocols[0].set_caps(0, 200).unwrap();
+        vault.protocols[3].set_caps(1_000, 0).unwrap();
+        vault.refresh_protocol_weights().unwrap();
+        let weights: Vec<u32> = vault.protocols.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, vec![2_000, 5_000, 0, 1_000]);
+    }
+
+    fn rewards_vault(weight_strategy: WeightStrategy) -> VaultAccount {
+        let protocol = |weight: u32, deposited: u64, rewards: i64| ProtocolData {
+            weight,
+            amount: deposited,
+            rewards: AccumulatedRewards {
+                amount: rewards,
+                deposited_avg_wad: deposited as u128 * WAD as u128,
+                ..AccumulatedRewards::default()
+            },
+            ..ProtocolData::default()
+        };
+
+        VaultAccount {
+            current_tvl: 5_000,
+            weight_strategy,
+            protocols: vec![
+                protocol(3_000, 1_000, 10),
+                protocol(5_000, 3_000, 60),
+                protocol(2_000, 1_000, -5),
+                protocol(0, 0, 0),
+            ],
+            ..VaultAccount::default()
+        }
+    }
+
+    fn weights(vault: &VaultAccount) -> Vec<u32> {
+        vault.protocols.iter().map(|p| p.weight).collect()
+    }
+
+    #[test]
+    fn test_rewards_per_deposit() {
+        let vault = rewards_vault(WeightStrategy::Auto);
+        let yields = vault
+            .protocols
+            .iter()
+            .map(|p| p.rewards.rewards_per_deposit_wad().unwrap())
+            .collect::<Vec<u128>>();
+        assert_eq!(yields, vec![WAD as u128 / 100, WAD as u128 / 50, 0, 0]);
+    }
+
+    #[test]
+    fn test_weight_strategy_auto() {
+        let mut vault = rewards_vault(WeightStrategy::Auto);
+        vault.refresh_protocol_weights().unwrap();
+        // The protocol with a loss keeps the minimum weight
+        assert_eq!(weights(&vault), vec![1_430, 8_560, 10, 0]);
+    }
+
+    #[test]
+    fn test_weight_strategy_manual() {
+        let mut vault = rewards_vault(WeightStrategy::Manual);
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![3_000, 5_000, 2_000, 0]);
+    }
+
+    #[test]
+    fn test_weight_strategy_equal_weight() {
+        let mut vault = rewards_vault(WeightStrategy::EqualWeight);
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![3_333, 3_333, 3_334, 0]);
+
+        // The excess over the caps is spread over the other protocols
+        vault.protocols[0].set_caps(1_000, 0).unwrap();
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![1_000, 4_500, 4_500, 0]);
+    }
+
+    #[test]
+    fn test_weight_strategy_max_yield() {
+        let mut vault = rewards_vault(WeightStrategy::MaxYield);
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![10, 9_980, 10, 0]);
+
+        // Nothing is moved without rewards
+        let mut vault = rewards_vault(WeightStrategy::MaxYield);
+        vault.protocols[0].rewards.amount = 0;
+        vault.protocols[1].rewards.amount = -10;
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![3_000, 5_000, 2_000, 0]);
+    }
+
+    #[test]
+    fn test_weight_strategy_risk_adjusted() {
+        let mut vault = rewards_vault(WeightStrategy::RiskAdjusted);
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![3_333, 6_657, 10, 0]);
+
+        // Twice the yield of the first protocol with four times its risk
+        let mut vault = rewards_vault(WeightStrategy::RiskAdjusted);
+        vault.protocols[0].set_risk_score(1);
+        vault.protocols[1].set_risk_score(4);
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![6_657, 3_333, 10, 0]);
+    }
+
+    #[test]
+    fn test_limit_weight_changes() {
+        let mut vault = rewards_vault(WeightStrategy::MaxYield);
+        vault.damping.max_weight_delta = 1_000;
+        vault.refresh_protocol_weights().unwrap();
+        // The weight released by the first protocol is moved to the second one
+        assert_eq!(weights(&vault), vec![2_000, 6_000, 1_000, 0]);
+
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![1_000, 7_000, 10, 0]);
+
+        // The increases are scaled down to the released weight
+        let mut vault = VaultAccount {
+            damping: DampingParams {
+                max_weight_delta: 1_000,
+                ..DampingParams::default()
+            },
+            protocols: vec![
+                ProtocolData {
+                    weight: 10,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 5_000,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 4_990,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+        vault.limit_weight_changes(&[6_000, 2_000, 2_000]).unwrap();
+        assert_eq!(weights(&vault), vec![5_000, 2_500, 2_500]);
+
+        vault.damping.max_weight_delta = 0;
+        vault.protocols[0].weight = 10;
+        vault.limit_weight_changes(&[6_000, 2_000, 2_000]).unwrap();
+        assert_eq!(weights(&vault), vec![10, 2_500, 2_500]);
+
+        // A lowered cap is applied at once
+        let mut vault = VaultAccount {
+            weight_strategy: WeightStrategy::Manual,
+            damping: DampingParams {
+                max_weight_delta: 1_000,
+                ..DampingParams::default()
+            },
+            protocols: vec![
+                ProtocolData {
+                    weight: 6_000,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 4_000,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+        vault.protocols[0].set_caps(2_000, 0).unwrap();
+        vault.refresh_protocol_weights().unwrap();
+        assert_eq!(weights(&vault), vec![2_000, 5_000]);
+    }
+
+    #[test]
+    fn test_min_deviation() {
+        let mut vault = VaultAccount {
+            current_tvl: 10_000,
+            protocols: vec![
+                ProtocolData {
+                    weight: 5_000,
+                    amount: 5_040,
+                    ..ProtocolData::default()
+                },
+                ProtocolData {
+                    weight: 5_000,
+                    amount: 4_960,
+                    ..ProtocolData::default()
+                },
+            ],
+            ..VaultAccount::default()
+        };
+        assert_eq!(vault.calculate_withdraw(0).unwrap(), 40);
+        assert_eq!(vault.calculate_deposit(1, 1_000).unwrap(), 40);
+
+        // 40 tokens are below 0.5% of the invested TVL
+        vault.damping.min_deviation = 50;
+        assert!(vault.damping.validate().is_ok());
+        assert!(vault.calculate_withdraw(0).is_err());
+        assert!(vault.calculate_deposit(1, 1_000).is_err());
+        assert_eq!(vault.deposit_excess(0).unwrap(), 0);
+        assert_eq!(vault.deposit_shortfall(1).unwrap(), 0);
+
+        vault.damping.min_deviation = 40;
+        assert_eq!(vault.calculate_withdraw(0).unwrap(), 40);
+
+        vault.damping.min_deviation = 41;
+        vault.damping.min_deviation_unit = DeviationUnit::Tokens;
+        assert!(vault.calculate_withdraw(0).is_err());
+        vault.damping.min_deviation = 40;
+        assert_eq!(vault.calculate_deposit(1, 1_000).unwrap(), 40);
+
+        vault.damping.min_deviation = WEIGHTS_SCALE as u64 + 1;
+        assert!(vault.damping.validate().is_ok());
+        vault.damping.min_deviation_unit = DeviationUnit::Bps;
+        assert!(vault.damping.validate().is_err());
+        vault.damping.min_deviation = 0;
+        vault.damping.max_weight_delta = WEIGHTS_SCALE as u16 + 1;
+        assert!(vault.damping.validate().is_err());
+    }
+
+    #[test]
+    fn test_pause_flags_bits() {
+        let flags = PauseFlags {
+            withdraw: true,
+            rebalance: true,
+            ..PauseFlags::default()
+        };
+        let decoded = PauseFlags::from_bits(flags.bits());
+        assert!(!decoded.deposit);
+        assert!(decoded.withdraw);
+        assert!(!decoded.open_ticket);
+        assert!(decoded.rebalance);
+
+        // Legacy is_paused = true
+        let decoded = PauseFlags::from_bits(PauseFlags::ALL);
+        assert!(decoded.deposit && decoded.withdraw && decoded.open_ticket && decoded.rebalance);
+        assert_eq!(PauseFlags::from_bits(0).bits(), 0);
+    }
+
+    /// Vault account layout before the pause flags
+    #[derive(AnchorSerialize)]
+    struct BaselineVaultAccount {
+        version: u8,
+        is_paused: bool,
+        seed_number: u8,
+        bumps: Bumps,
+        input_mint_pubkey: Pubkey,
+        dao_treasury_lp_token_account: Pubkey,
+        last_refresh_time: i64,
+        refresh: RefreshParams,
+        current_tvl: u64,
+        rewards_sum: u64,
+        previous_lp_price: LpPrice,
+        bump_ticket_mint: u8,
+        _padding1: [u8; 7],
+        _padding2: [u64; 7],
+        protocols: Vec<BaselineProtocolData>,
+    }
+
+    #[derive(AnchorSerialize)]
+    struct BaselineProtocolData {
+        protocol_id: u8,
+        hash_pubkey: HashPubkey,
+        weight: u32,
+        amount: u64,
+        rewards: AccumulatedRewards,
+        _padding: [u64; 5],
+    }
+
+    #[test]
+    fn test_deserialize_baseline_vault() {
+        let input_mint_pubkey = Pubkey::new_unique();
+        let dao_treasury_lp_token_account = Pubkey::new_unique();
+        let baseline = BaselineVaultAccount {
+            version: 1,
+            is_paused: true,
+            seed_number: 3,
+            bumps: Bumps {
+                vault: 254,
+                lp_token_mint: 253,
+            },
+            input_mint_pubkey,
+            dao_treasury_lp_token_account,
+            last_refresh_time: 1_000,
+            refresh: RefreshParams {
+                min_elapsed_time: 3000,
+                min_deposit_lamports: 10,
+            },
+            current_tvl: 5_000,
+            rewards_sum: 20,
+            previous_lp_price: LpPrice {
+                total_tokens: 5_000,
+                minted_tokens: 4_000,
+            },
+            bump_ticket_mint: 252,
+            _padding1: [0; 7],
+            _padding2: [0; 7],
+            protocols: vec![BaselineProtocolData {
+                protocol_id: 2,
+                hash_pubkey: HashPubkey::default(),
+                weight: 10_000,
+                amount: 5_000,
+                rewards: AccumulatedRewards::default(),
+                _padding: [0; 5],
+            }],
+        };
+        let bytes = baseline.try_to_vec().unwrap();
+        assert_eq!(
+            bytes.len(),
+            VaultAccount::SIZE - ProtocolData::SIZE * 9,
+            "layout size changed"
+        );
+
+        let vault = VaultAccount::try_from_slice(&bytes).unwrap();
+        assert_eq!(vault.version, 1);
+        let paused = vault.pause_flags();
+        assert!(paused.deposit && paused.withdraw && paused.open_ticket && paused.rebalance);
+        assert_eq!(vault.seed_number, 3);
+        assert_eq!(vault.bumps.vault, 254);
+        assert_eq!(vault.bumps.lp_token_mint, 253);
+        assert_eq!(vault.input_mint_pubkey, input_mint_pubkey);
+        assert_eq!(
+            vault.dao_treasury_lp_token_account,
+            dao_treasury_lp_token_account
+        );
+        assert_eq!(vault.last_refresh_time, 1_000);
+        assert_eq!(vault.refresh.min_deposit_lamports, 10);
+        assert_eq!(vault.current_tvl, 5_000);
+        assert_eq!(vault.rewards_sum, 20);
+        assert_eq!(vault.previous_lp_price.minted_tokens, 4_000);
+        assert_eq!(vault.bump_ticket_mint, 252);
+        assert_eq!(vault.pending_loss, 0);
+        assert_eq!(vault.weight_strategy, WeightStrategy::Auto);
+        assert_eq!(vault.damping.min_deviation_unit, DeviationUnit::Bps);
+        assert_eq!(vault.protocols.len(), 1);
+        assert_eq!(vault.protocols[0].protocol_id, 2);
+        assert_eq!(vault.protocols[0].weight, 10_000);
+        assert_eq!(vault.protocols[0].amount, 5_000);
+        assert_eq!(vault.protocols[0].max_weight, 0);
+    }
 }
@@ -0,0 +1,74 @@
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+
+/// Maximum number of vaults per input mint (one for each seed number)
+pub const MAX_VAULTS_PER_MINT: usize = 256;
+
+/// Index of the vaults created for an input mint
+#[account]
+#[derive(Default)]
+pub struct VaultIndexAccount {
+    /// PDA bump seed
+    pub bump: u8,
+    /// Input token mint address
+    pub input_mint_pubkey: Pubkey,
+    /// Bitmap of the seed numbers of the vaults created for the input mint
+    pub seed_numbers: [u64; 4],
+}
+
+impl VaultIndexAccount {
+    pub const SIZE: usize = 1 + 32 + 8 * 4;
+
+    /// Add the vault seed number to the index
+    pub fn insert(&mut self, seed_number: u8) -> Result<()> {
+        let (word, bit) = Self::position(seed_number);
+        require!(
+            self.seed_numbers[word] & bit == 0,
+            ErrorCode::VaultAlreadyIndexed
+        );
+        self.seed_numbers[word] |= bit;
+        Ok(())
+    }
+
+    /// Check if a vault with the given seed number exists for the input mint
+    pub fn contains(&self, seed_number: u8) -> bool {
+        let (word, bit) = Self::position(seed_number);
+        self.seed_numbers[word] & bit != 0
+    }
+
+    /// Seed numbers of the vaults created for the input mint, in ascending order
+    pub fn seed_numbers(&self) -> Vec<u8> {
+        (0..MAX_VAULTS_PER_MINT)
+            .map(|seed_number| seed_number as u8)
+            .filter(|seed_number| self.contains(*seed_number))
+            .collect()
+    }
+
+    fn position(seed_number: u8) -> (usize, u64) {
+        ((seed_number / 64) as usize, 1 << (seed_number % 64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vault_index() {
+        let mut index = VaultIndexAccount::default();
+        assert!(index.seed_numbers().is_empty());
+
+        index.insert(0).unwrap();
+        index.insert(255).unwrap();
+        index.insert(64).unwrap();
+
+        assert!(index.contains(0));
+        assert!(index.contains(64));
+        assert!(index.contains(255));
+        assert!(!index.contains(1));
+        assert!(!index.contains(63));
+        assert_eq!(index.seed_numbers(), vec![0, 64, 255]);
+
+        assert!(index.insert(64).is_err());
+    }
+}
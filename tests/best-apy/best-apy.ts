@@ -35,16 +35,47 @@ describe("best_apy (" + INPUT_TOKEN + ")", () => {
 
   program.setToken(INPUT_TOKEN);
 
+  it("Initialize config", async () => {
+    const [configAccount, _bump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("config")],
+        program.programId
+      );
+
+    const tx = await program.methods
+      .initializeConfig()
+      .accounts({
+        userSigner,
+        configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  });
+
   it("Initialize vault with weights", async () => {
     const tx = await program.initializeVault(new anchor.BN(0));
 
+    const [configAccount, _configBump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("config")],
+        program.programId
+      );
+    const [registryAccount, _registryBump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("registry")],
+        program.programId
+      );
+
     const txProtocols = await Promise.all(
       PROTOCOLS.map(async (protocol) =>
         program.methods
           .addProtocol(protocol)
           .accounts({
             userSigner,
+            configAccount,
             vaultAccount: program.vaultKeys[INPUT_TOKEN].vaultAccount,
+            registryAccount,
           })
           .transaction()
       )
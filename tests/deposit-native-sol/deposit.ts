@@ -20,6 +20,24 @@ describe("deposit-from-native", () => {
 
   const program = client.BestApy;
 
+  it("Initialize config", async () => {
+    const [configAccount, _bump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("config")],
+        program.programId
+      );
+
+    const tx = await program.methods
+      .initializeConfig()
+      .accounts({
+        userSigner,
+        configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  });
+
   it("Initialize an empty vault", async () => {
     const tx = await program.initializeVault(new anchor.BN(0));
     await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
@@ -87,4 +105,65 @@ describe("deposit-from-native", () => {
 
     assert.deepStrictEqual(amount, lpAmount);
   });
+
+  it("Withdraw to native", async () => {
+    const vaultAccount = program.vaultKeys[INPUT_TOKEN].vaultAccount;
+    const vaultLpTokenMintPubkey =
+      program.vaultKeys[INPUT_TOKEN].vaultLpTokenMintAddress;
+
+    const userLpTokenAccount = await spl.getAssociatedTokenAddress(
+      vaultLpTokenMintPubkey,
+      userSigner,
+      false
+    );
+    const [userWrappedAccount, _bump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from("wrapped"),
+          vaultAccount.toBuffer(),
+          userSigner.toBuffer(),
+        ],
+        program.programId
+      );
+
+    const vaultData = await program.decodeVault();
+    const lpAmount = new anchor.BN(500_000_000);
+
+    const balanceBefore = await program.provider.connection.getBalance(
+      userSigner
+    );
+
+    const tx = await program.methods
+      .withdrawToNative(lpAmount, new anchor.BN(0))
+      .accounts({
+        userSigner,
+        userWrappedAccount,
+        userLpTokenAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount: await spl.getAssociatedTokenAddress(
+          spl.NATIVE_MINT,
+          vaultAccount,
+          true
+        ),
+        daoTreasuryLpTokenAccount: vaultData.daoTreasuryLpTokenAccount,
+        nativeMint: spl.NATIVE_MINT,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    // The temporary wrapped account is closed
+    const userWrappedAccountInfo =
+      await program.provider.connection.getAccountInfo(userWrappedAccount);
+    assert.isNull(userWrappedAccountInfo);
+
+    // The user receives the withdrawn SOL minus the tx fees
+    const balanceAfter = await program.provider.connection.getBalance(
+      userSigner
+    );
+    assert.isTrue(balanceAfter - balanceBefore > 499_000_000);
+  });
 });
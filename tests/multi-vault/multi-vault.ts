@@ -0,0 +1,217 @@
+import * as anchor from "@project-serum/anchor";
+import * as spl from "@solana/spl-token";
+import { assert } from "chai";
+import { GoblinGold } from "goblin-sdk-local";
+
+const CONFIRM_OPTS: anchor.web3.ConfirmOptions = {
+  skipPreflight: true,
+};
+
+describe("multi-vault", () => {
+  const provider = anchor.Provider.local();
+  const userSigner = provider.wallet.publicKey;
+
+  const client = new GoblinGold({
+    connection: provider.connection,
+    wallet: provider.wallet,
+  });
+
+  const program = client.BestApy;
+  const inputTokenMintAddress = spl.NATIVE_MINT;
+
+  let configAccount: anchor.web3.PublicKey;
+  let vaultIndexAccount: anchor.web3.PublicKey;
+  let registryAccount: anchor.web3.PublicKey;
+
+  async function vaultAddresses(seedNumber: number) {
+    const [vaultAccount, _vaultBump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from("vault"),
+          Buffer.from([seedNumber]),
+          inputTokenMintAddress.toBuffer(),
+        ],
+        program.programId
+      );
+    const [vaultLpTokenMintPubkey, _mintBump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("mint"), vaultAccount.toBuffer()],
+        program.programId
+      );
+    return { vaultAccount, vaultLpTokenMintPubkey };
+  }
+
+  async function initializeVault(seedNumber: number) {
+    const { vaultAccount, vaultLpTokenMintPubkey } = await vaultAddresses(
+      seedNumber
+    );
+
+    const tx = await program.methods
+      .initializeVault(seedNumber)
+      .accounts({
+        userSigner,
+        configAccount,
+        inputTokenMintAddress,
+        vaultAccount,
+        vaultIndexAccount,
+        registryAccount,
+        vaultInputTokenAccount: await spl.getAssociatedTokenAddress(
+          inputTokenMintAddress,
+          vaultAccount,
+          true
+        ),
+        vaultLpTokenMintPubkey,
+        daoTreasuryLpTokenAccount: await spl.getAssociatedTokenAddress(
+          vaultLpTokenMintPubkey,
+          userSigner,
+          false
+        ),
+        daoTreasuryOwner: userSigner,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        associatedTokenProgram: spl.ASSOCIATED_TOKEN_PROGRAM_ID,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  }
+
+  it("Initialize config", async () => {
+    [configAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("config")],
+      program.programId
+    );
+    [vaultIndexAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("vault_index"), inputTokenMintAddress.toBuffer()],
+      program.programId
+    );
+    [registryAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("registry")],
+      program.programId
+    );
+
+    const tx = await program.methods
+      .initializeConfig()
+      .accounts({
+        userSigner,
+        configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  });
+
+  it("Initialize two vaults for the same mint", async () => {
+    await initializeVault(0);
+    await initializeVault(1);
+
+    for (const seedNumber of [0, 1]) {
+      const { vaultAccount } = await vaultAddresses(seedNumber);
+      const vaultData = await program.account.vaultAccount.fetch(vaultAccount);
+      assert.strictEqual(vaultData.seedNumber, seedNumber);
+      assert.isTrue(vaultData.inputMintPubkey.equals(inputTokenMintAddress));
+    }
+
+    const indexData = await program.account.vaultIndexAccount.fetch(
+      vaultIndexAccount
+    );
+    assert.isTrue(indexData.inputMintPubkey.equals(inputTokenMintAddress));
+    assert.strictEqual(indexData.seedNumbers[0].toNumber(), 0b11);
+  });
+
+  it("Operate the vault with a non-zero seed number", async () => {
+    const { vaultAccount } = await vaultAddresses(1);
+
+    const tx = await program.methods
+      .setRefreshParams({
+        minElapsedTime: new anchor.BN(0),
+        minDepositLamports: new anchor.BN(0),
+      })
+      .accounts({ userSigner, configAccount, vaultAccount })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const vaultData = await program.account.vaultAccount.fetch(vaultAccount);
+    assert.strictEqual(vaultData.refresh.minElapsedTime.toNumber(), 0);
+  });
+
+  it("List the vaults in the registry", async () => {
+    const registryData = await program.account.registryAccount.fetch(
+      registryAccount
+    );
+    assert.strictEqual(registryData.vaults.length, 2);
+
+    for (const seedNumber of [0, 1]) {
+      const { vaultAccount, vaultLpTokenMintPubkey } = await vaultAddresses(
+        seedNumber
+      );
+      const entry = registryData.vaults[seedNumber];
+      assert.isTrue(entry.vault.equals(vaultAccount));
+      assert.isTrue(entry.inputMintPubkey.equals(inputTokenMintAddress));
+      assert.isTrue(entry.lpTokenMintPubkey.equals(vaultLpTokenMintPubkey));
+      assert.strictEqual(entry.seedNumber, seedNumber);
+      assert.deepStrictEqual(entry.status, { active: {} });
+    }
+  });
+
+  it("Registering a registered vault fails", async () => {
+    const { vaultAccount, vaultLpTokenMintPubkey } = await vaultAddresses(0);
+
+    try {
+      const tx = await program.methods
+        .registerVault()
+        .accounts({
+          userSigner,
+          configAccount,
+          vaultAccount,
+          vaultLpTokenMintPubkey,
+          vaultIndexAccount,
+          registryAccount,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .transaction();
+      await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+      assert.fail("The vault should already be registered");
+    } catch (err) {
+      assert.notInclude(
+        err.toString(),
+        "The vault should already be registered"
+      );
+    }
+
+    const registryData = await program.account.registryAccount.fetch(
+      registryAccount
+    );
+    assert.strictEqual(registryData.vaults.length, 2);
+
+    const indexData = await program.account.vaultIndexAccount.fetch(
+      vaultIndexAccount
+    );
+    assert.strictEqual(indexData.seedNumbers[0].toNumber(), 0b11);
+  });
+
+  it("Delist a vault", async () => {
+    const { vaultAccount } = await vaultAddresses(1);
+
+    const tx = await program.methods
+      .delistVault()
+      .accounts({ userSigner, configAccount, vaultAccount, registryAccount })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const registryData = await program.account.registryAccount.fetch(
+      registryAccount
+    );
+    assert.deepStrictEqual(registryData.vaults[0].status, { active: {} });
+    assert.deepStrictEqual(registryData.vaults[1].status, { delisted: {} });
+  });
+
+  it("Initializing an existing vault fails", async () => {
+    try {
+      await initializeVault(1);
+      assert.fail("The vault should already exist");
+    } catch (err) {
+      assert.notInclude(err.toString(), "The vault should already exist");
+    }
+  });
+});
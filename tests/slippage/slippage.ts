@@ -0,0 +1,446 @@
+import * as anchor from "@project-serum/anchor";
+import * as spl from "@solana/spl-token";
+import { assert } from "chai";
+import { GoblinGold, Protocols, TOKENS, decodeAccount } from "goblin-sdk-local";
+
+const INPUT_TOKEN = "WSOL";
+const INPUT_TOKEN_MINT = new anchor.web3.PublicKey(
+  TOKENS[INPUT_TOKEN].mintAddress
+);
+
+const CONFIRM_OPTS: anchor.web3.ConfirmOptions = {
+  skipPreflight: true,
+};
+
+// Simulate the failing txs in order to get the program logs
+const CONFIRM_OPTS_PREFLIGHT: anchor.web3.ConfirmOptions = {
+  skipPreflight: false,
+};
+
+const WEIGHTS_SCALE = 10_000;
+
+const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+async function assertSlippageExceeded(promise: Promise<unknown>) {
+  try {
+    await promise;
+  } catch (err) {
+    const logs: string[] = err.logs ?? [];
+    assert.isTrue(
+      logs.some((log) => log.includes("SlippageExceeded")),
+      "Expected SlippageExceeded error"
+    );
+    return;
+  }
+  assert.fail("The transaction should have failed");
+}
+
+describe("slippage", () => {
+  const provider = anchor.Provider.local();
+  const userSigner = provider.wallet.publicKey;
+
+  const client = new GoblinGold({
+    connection: provider.connection,
+    wallet: provider.wallet,
+  });
+
+  const program = client.BestApy;
+
+  program.setToken(INPUT_TOKEN);
+
+  const amount = new anchor.BN(1_000_000_000);
+  const wrappedKeypair = anchor.web3.Keypair.generate();
+  const userWrappedAccount = wrappedKeypair.publicKey;
+
+  let configAccount: anchor.web3.PublicKey;
+  let vaultAccount: anchor.web3.PublicKey;
+  let vaultLpTokenMintPubkey: anchor.web3.PublicKey;
+  let vaultInputTokenAccount: anchor.web3.PublicKey;
+  let daoTreasuryLpTokenAccount: anchor.web3.PublicKey;
+  let userLpTokenAccount: anchor.web3.PublicKey;
+  let solendWithdrawIx: anchor.web3.TransactionInstruction;
+  let tulipWithdrawIx: anchor.web3.TransactionInstruction;
+
+  async function refreshWeights() {
+    const tx = await program.methods
+      .refreshWeights()
+      .accounts({
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        daoTreasuryLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  }
+
+  async function withdrawTx(
+    lpAmount: anchor.BN,
+    minTokensOut: anchor.BN
+  ): Promise<anchor.web3.Transaction> {
+    return program.methods
+      .withdraw(lpAmount, minTokensOut)
+      .accounts({
+        userSigner,
+        userInputTokenAccount: userWrappedAccount,
+        userLpTokenAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount,
+        daoTreasuryLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+  }
+
+  async function withdrawExactTokensIx(
+    amount: anchor.BN
+  ): Promise<anchor.web3.TransactionInstruction> {
+    return program.methods
+      .withdrawExactTokens(amount, new anchor.BN("18446744073709551615"))
+      .accounts({
+        userSigner,
+        userInputTokenAccount: userWrappedAccount,
+        userLpTokenAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount,
+        daoTreasuryLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .instruction();
+  }
+
+  async function depositIdle(depositAmount: anchor.BN) {
+    const tx = await program.methods
+      .depositFromNative(depositAmount, new anchor.BN(0))
+      .accounts({
+        userSigner,
+        userWrappedAccount,
+        userLpTokenAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount,
+        daoTreasuryLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  }
+
+  async function tokenAmount(address: anchor.web3.PublicKey) {
+    const accountInfo = await program.provider.connection.getAccountInfo(
+      address
+    );
+    return new anchor.BN(decodeAccount(accountInfo.data).amount);
+  }
+
+  async function protocolAmount(protocol: Protocols): Promise<anchor.BN> {
+    const vaultData = await program.decodeVault();
+    const data = vaultData.protocols.find((p) => p.protocolId === protocol);
+    return new anchor.BN(data.amount);
+  }
+
+  async function quoteWithdraw(lpAmount: anchor.BN): Promise<anchor.BN> {
+    const vaultData = await program.decodeVault();
+    const price = vaultData.previousLpPrice;
+    return lpAmount
+      .mul(new anchor.BN(price.totalTokens))
+      .div(new anchor.BN(price.mintedTokens))
+      .subn(1);
+  }
+
+  it("Initialize config", async () => {
+    [configAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("config")],
+      program.programId
+    );
+
+    const tx = await program.methods
+      .initializeConfig()
+      .accounts({
+        userSigner,
+        configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  });
+
+  it("Initialize an empty vault with management fees", async () => {
+    const tx = await program.initializeVault(new anchor.BN(0));
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    vaultAccount = program.vaultKeys[INPUT_TOKEN].vaultAccount;
+    vaultLpTokenMintPubkey =
+      program.vaultKeys[INPUT_TOKEN].vaultLpTokenMintAddress;
+    vaultInputTokenAccount = await spl.getAssociatedTokenAddress(
+      INPUT_TOKEN_MINT,
+      vaultAccount,
+      true
+    );
+    userLpTokenAccount = await spl.getAssociatedTokenAddress(
+      vaultLpTokenMintPubkey,
+      userSigner,
+      false
+    );
+
+    const vaultData = await program.decodeVault();
+    daoTreasuryLpTokenAccount = vaultData.daoTreasuryLpTokenAccount;
+
+    const txParams = new anchor.web3.Transaction()
+      .add(
+        await program.methods
+          .setRefreshParams({
+            minElapsedTime: new anchor.BN(0),
+            minDepositLamports: new anchor.BN(0),
+          })
+          .accounts({ userSigner, configAccount, vaultAccount })
+          .transaction()
+      )
+      .add(
+        await program.methods
+          .setFees({
+            performanceFeeBps: 0,
+            managementFeeBps: 500,
+            depositFeeBps: 0,
+            withdrawFeeBps: 0,
+          })
+          .accounts({ userSigner, configAccount, vaultAccount })
+          .transaction()
+      );
+    await program.provider.sendAndConfirm(txParams, [], CONFIRM_OPTS);
+  });
+
+  it("Create user accounts", async () => {
+    const lamports = await spl.getMinimumBalanceForRentExemptAccount(
+      program.provider.connection
+    );
+
+    const tx = new anchor.web3.Transaction().add(
+      anchor.web3.SystemProgram.createAccount({
+        fromPubkey: userSigner,
+        newAccountPubkey: userWrappedAccount,
+        space: spl.ACCOUNT_SIZE,
+        lamports,
+        programId: spl.TOKEN_PROGRAM_ID,
+      }),
+      spl.createInitializeAccountInstruction(
+        userWrappedAccount,
+        spl.NATIVE_MINT,
+        userSigner
+      ),
+      spl.createAssociatedTokenAccountInstruction(
+        userSigner,
+        userLpTokenAccount,
+        userSigner,
+        vaultLpTokenMintPubkey
+      )
+    );
+    await program.provider.sendAndConfirm(tx, [wrappedKeypair], CONFIRM_OPTS);
+  });
+
+  it("Deposit reverts when receiving less LPs than expected", async () => {
+    const tx = await program.methods
+      .depositFromNative(amount, amount.addn(1))
+      .accounts({
+        userSigner,
+        userWrappedAccount,
+        userLpTokenAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount,
+        daoTreasuryLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+
+    await assertSlippageExceeded(
+      program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS_PREFLIGHT)
+    );
+  });
+
+  it("Deposit within the slippage tolerance", async () => {
+    const tx = await program.methods
+      .depositFromNative(amount, amount)
+      .accounts({
+        userSigner,
+        userWrappedAccount,
+        userLpTokenAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount,
+        daoTreasuryLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const userLpTokenAccountInfo =
+      await program.provider.connection.getAccountInfo(userLpTokenAccount);
+    const data = decodeAccount(userLpTokenAccountInfo.data);
+    assert.deepStrictEqual(new anchor.BN(data.amount), amount);
+
+    // Set the initial refresh time and LP price
+    await refreshWeights();
+  });
+
+  it("Withdraw reverts if the LP price moves before landing", async () => {
+    const lpAmount = amount.divn(2);
+    const minTokensOut = await quoteWithdraw(lpAmount);
+    const tx = await withdrawTx(lpAmount, minTokensOut);
+
+    // Management fees accrue meanwhile, decreasing the LP price
+    await sleep(5_000);
+    await refreshWeights();
+
+    await assertSlippageExceeded(
+      program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS_PREFLIGHT)
+    );
+  });
+
+  it("Withdraw with an updated quote", async () => {
+    const lpAmount = amount.divn(2);
+    const minTokensOut = await quoteWithdraw(lpAmount);
+    const tx = await withdrawTx(lpAmount, minTokensOut);
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const userWrappedAccountInfo =
+      await program.provider.connection.getAccountInfo(userWrappedAccount);
+    const data = decodeAccount(userWrappedAccountInfo.data);
+    assert.isTrue(new anchor.BN(data.amount).gte(minTokensOut));
+  });
+
+  it("Deposit into Solend and Tulip", async () => {
+    const [registryAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("registry")],
+      program.programId
+    );
+
+    const tx = new anchor.web3.Transaction();
+    for (const protocol of [Protocols.Solend, Protocols.Tulip]) {
+      tx.add(
+        await program.methods
+          .addProtocol(protocol)
+          .accounts({
+            userSigner,
+            configAccount,
+            vaultAccount,
+            registryAccount,
+          })
+          .transaction()
+      );
+    }
+    tx.add(
+      await program.setProtocolWeights([WEIGHTS_SCALE / 2, WEIGHTS_SCALE / 2])
+    );
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const txsAccounts = await program.initializeProtocolAccounts();
+    await Promise.all(
+      txsAccounts.map(async (tx) =>
+        program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS)
+      )
+    );
+
+    const txsHashes = await program.setHashes();
+    const txHashes = txsHashes.reduce(
+      (acc, tx) => acc.add(tx),
+      new anchor.web3.Transaction()
+    );
+    await program.provider.sendAndConfirm(txHashes, [], CONFIRM_OPTS);
+
+    await depositIdle(amount);
+
+    const [_txsWithdraw, txsDeposit] = await program.rebalance();
+    await Promise.all(
+      txsDeposit.map(async (tx) =>
+        program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS)
+      )
+    );
+
+    assert.isTrue((await protocolAmount(Protocols.Solend)).gtn(0));
+    assert.isTrue((await protocolAmount(Protocols.Tulip)).gtn(0));
+
+    // Take the protocol withdrawals routed by the SDK for the whole user position
+    const txsWithdraw = await program.withdraw({
+      userInputTokenAccount: userWrappedAccount,
+      userLpTokenAccount,
+      lpAmount: await tokenAmount(userLpTokenAccount),
+    });
+    for (const ix of txsWithdraw.flatMap((tx) => tx.instructions)) {
+      if (!ix.programId.equals(program.programId) || ix.data.length !== 8) {
+        continue;
+      }
+      const decoded = program.coder.instruction.decode(ix.data);
+      if (decoded?.name === "solendWithdraw") {
+        solendWithdrawIx = solendWithdrawIx ?? ix;
+      } else if (decoded?.name === "tulipWithdraw") {
+        tulipWithdrawIx = tulipWithdrawIx ?? ix;
+      }
+    }
+    assert.isDefined(solendWithdrawIx);
+    assert.isDefined(tulipWithdrawIx);
+  });
+
+  it("Chained protocol withdrawals only pull the remaining shortfall", async () => {
+    const idleBefore = await tokenAmount(vaultInputTokenAccount);
+    const solendBefore = await protocolAmount(Protocols.Solend);
+    const tulipBefore = await protocolAmount(Protocols.Tulip);
+
+    // Solend alone cannot cover the withdrawal, so Tulip pulls the rest
+    const withdrawAmount = idleBefore
+      .add(solendBefore)
+      .add(tulipBefore.divn(2));
+    const tx = new anchor.web3.Transaction()
+      .add(solendWithdrawIx)
+      .add(tulipWithdrawIx)
+      .add(await withdrawExactTokensIx(withdrawAmount));
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS_PREFLIGHT);
+
+    const solendAfter = await protocolAmount(Protocols.Solend);
+    const tulipAfter = await protocolAmount(Protocols.Tulip);
+    const idleAfter = await tokenAmount(vaultInputTokenAccount);
+
+    // Up to a few lamports lost to the collateral conversions
+    const solendWithdrawn = solendBefore.sub(solendAfter);
+    const tulipWithdrawn = tulipBefore.sub(tulipAfter);
+    assert.isTrue(solendAfter.lten(1));
+    assert.isTrue(
+      tulipWithdrawn.sub(tulipBefore.divn(2)).abs().lten(3),
+      "Tulip should only cover the shortfall left by Solend"
+    );
+    assert.isTrue(
+      idleBefore
+        .add(solendWithdrawn)
+        .add(tulipWithdrawn)
+        .sub(withdrawAmount)
+        .sub(idleAfter)
+        .abs()
+        .lten(3)
+    );
+    assert.isTrue(idleAfter.lten(3));
+  });
+
+  it("Chained protocol withdrawal without shortfall is a no-op", async () => {
+    await depositIdle(amount.divn(10));
+
+    const idleBefore = await tokenAmount(vaultInputTokenAccount);
+    const tulipBefore = await protocolAmount(Protocols.Tulip);
+
+    const withdrawAmount = idleBefore.divn(2);
+    const tx = new anchor.web3.Transaction()
+      .add(tulipWithdrawIx)
+      .add(await withdrawExactTokensIx(withdrawAmount));
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS_PREFLIGHT);
+
+    assert.deepStrictEqual(await protocolAmount(Protocols.Tulip), tulipBefore);
+    assert.deepStrictEqual(
+      await tokenAmount(vaultInputTokenAccount),
+      idleBefore.sub(withdrawAmount)
+    );
+  });
+});
@@ -0,0 +1,517 @@
+import * as anchor from "@project-serum/anchor";
+import * as spl from "@solana/spl-token";
+import { assert } from "chai";
+import { GoblinGold, TOKENS, decodeAccount } from "goblin-sdk-local";
+
+const INPUT_TOKEN = "WSOL";
+const INPUT_TOKEN_MINT = new anchor.web3.PublicKey(
+  TOKENS[INPUT_TOKEN].mintAddress
+);
+
+const WEIGHTS_SCALE = 10_000;
+const SETTLE_BOUNTY_BPS = 10;
+
+const CONFIRM_OPTS: anchor.web3.ConfirmOptions = {
+  skipPreflight: true,
+};
+
+// Simulate the failing txs in order to get the program logs
+const CONFIRM_OPTS_PREFLIGHT: anchor.web3.ConfirmOptions = {
+  skipPreflight: false,
+};
+
+async function assertProgramError(promise: Promise<unknown>, name: string) {
+  try {
+    await promise;
+  } catch (err) {
+    const logs: string[] = err.logs ?? [];
+    assert.isTrue(
+      logs.some((log) => log.includes(name)),
+      `Expected ${name} error`
+    );
+    return;
+  }
+  assert.fail("The transaction should have failed");
+}
+
+describe("withdraw-queue", () => {
+  const provider = anchor.Provider.local();
+  const userSigner = provider.wallet.publicKey;
+
+  const client = new GoblinGold({
+    connection: provider.connection,
+    wallet: provider.wallet,
+  });
+
+  const program = client.BestApy;
+
+  program.setToken(INPUT_TOKEN);
+
+  const amount = new anchor.BN(1_000_000_000);
+  const wrappedKeypair = anchor.web3.Keypair.generate();
+  const userWrappedAccount = wrappedKeypair.publicKey;
+
+  let configAccount: anchor.web3.PublicKey;
+  let vaultAccount: anchor.web3.PublicKey;
+  let vaultLpTokenMintPubkey: anchor.web3.PublicKey;
+  let vaultInputTokenAccount: anchor.web3.PublicKey;
+  let vaultLpTokenAccount: anchor.web3.PublicKey;
+  let vaultTicketMintPubkey: anchor.web3.PublicKey;
+  let vaultUserTicketAccount: anchor.web3.PublicKey;
+  let bumpUser: number;
+  let withdrawTicketAccount: anchor.web3.PublicKey;
+  let daoTreasuryLpTokenAccount: anchor.web3.PublicKey;
+  let userLpTokenAccount: anchor.web3.PublicKey;
+
+  async function withdrawEpochAddress(epoch: number) {
+    const [withdrawEpochAccount] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from("withdraw_epoch"),
+          vaultAccount.toBuffer(),
+          new anchor.BN(epoch).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+    return withdrawEpochAccount;
+  }
+
+  async function tokenAmount(address: anchor.web3.PublicKey) {
+    const accountInfo = await program.provider.connection.getAccountInfo(
+      address
+    );
+    return new anchor.BN(decodeAccount(accountInfo.data).amount);
+  }
+
+  async function openWithdrawTicketTx(lpAmount: anchor.BN, epoch: number) {
+    return program.methods
+      .openWithdrawTicket(bumpUser, lpAmount)
+      .accounts({
+        userSigner,
+        userLpTokenAccount,
+        vaultUserTicketAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultTicketMintPubkey,
+        vaultLpTokenAccount,
+        daoTreasuryLpTokenAccount,
+        withdrawTicketAccount,
+        withdrawEpochAccount: await withdrawEpochAddress(epoch),
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+  }
+
+  async function openWithdrawTicket(lpAmount: anchor.BN, epoch: number) {
+    const tx = await openWithdrawTicketTx(lpAmount, epoch);
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  }
+
+  async function fillWithdrawEpoch(fillAmount: anchor.BN, epoch: number) {
+    const tx = await program.methods
+      .fillWithdrawEpoch(fillAmount)
+      .accounts({
+        userSigner,
+        configAccount,
+        vaultAccount,
+        withdrawEpochAccount: await withdrawEpochAddress(epoch),
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount,
+        vaultLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  }
+
+  async function closeWithdrawTicket(epoch: number) {
+    const tx = await program.methods
+      .closeWithdrawTicket(bumpUser, new anchor.BN(0))
+      .accounts({
+        userSigner,
+        userInputTokenAccount: userWrappedAccount,
+        vaultUserTicketAccount,
+        vaultAccount,
+        vaultTicketMintPubkey,
+        vaultInputTokenAccount,
+        withdrawTicketAccount,
+        withdrawEpochAccount: await withdrawEpochAddress(epoch),
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  }
+
+  it("Initialize config", async () => {
+    [configAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("config")],
+      program.programId
+    );
+
+    const tx = await program.methods
+      .initializeConfig()
+      .accounts({
+        userSigner,
+        configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  });
+
+  it("Initialize an empty vault with the ticket mint", async () => {
+    const tx = await program.initializeVault(new anchor.BN(0));
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const txTicketMint = await program.initializeTicketMint();
+    await program.provider.sendAndConfirm(txTicketMint, [], CONFIRM_OPTS);
+
+    vaultAccount = program.vaultKeys[INPUT_TOKEN].vaultAccount;
+    vaultLpTokenMintPubkey =
+      program.vaultKeys[INPUT_TOKEN].vaultLpTokenMintAddress;
+    vaultTicketMintPubkey =
+      program.vaultKeys[INPUT_TOKEN].vaultTicketMintPubkey;
+    vaultInputTokenAccount = await spl.getAssociatedTokenAddress(
+      INPUT_TOKEN_MINT,
+      vaultAccount,
+      true
+    );
+    vaultLpTokenAccount = await spl.getAssociatedTokenAddress(
+      vaultLpTokenMintPubkey,
+      vaultAccount,
+      true
+    );
+    userLpTokenAccount = await spl.getAssociatedTokenAddress(
+      vaultLpTokenMintPubkey,
+      userSigner,
+      false
+    );
+    [vaultUserTicketAccount, bumpUser] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from("ticket_mint"),
+          vaultTicketMintPubkey.toBuffer(),
+          userSigner.toBuffer(),
+        ],
+        program.programId
+      );
+    [withdrawTicketAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from("withdraw_ticket"),
+        vaultAccount.toBuffer(),
+        userSigner.toBuffer(),
+      ],
+      program.programId
+    );
+
+    const vaultData = await program.decodeVault();
+    daoTreasuryLpTokenAccount = vaultData.daoTreasuryLpTokenAccount;
+  });
+
+  it("Create user and vault accounts", async () => {
+    const lamports = await spl.getMinimumBalanceForRentExemptAccount(
+      program.provider.connection
+    );
+
+    const tx = new anchor.web3.Transaction()
+      .add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: userSigner,
+          newAccountPubkey: userWrappedAccount,
+          space: spl.ACCOUNT_SIZE,
+          lamports,
+          programId: spl.TOKEN_PROGRAM_ID,
+        }),
+        spl.createInitializeAccountInstruction(
+          userWrappedAccount,
+          spl.NATIVE_MINT,
+          userSigner
+        ),
+        spl.createAssociatedTokenAccountInstruction(
+          userSigner,
+          userLpTokenAccount,
+          userSigner,
+          vaultLpTokenMintPubkey
+        ),
+        spl.createAssociatedTokenAccountInstruction(
+          userSigner,
+          vaultLpTokenAccount,
+          vaultAccount,
+          vaultLpTokenMintPubkey
+        )
+      )
+      .add(
+        await program.methods
+          .createVaultUserTicketAccount()
+          .accounts({
+            userSigner,
+            userTicketAccountOwner: userSigner,
+            vaultUserTicketAccount,
+            vaultAccount,
+            vaultTicketMintPubkey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .transaction()
+      );
+    await program.provider.sendAndConfirm(tx, [wrappedKeypair], CONFIRM_OPTS);
+  });
+
+  it("Deposit", async () => {
+    const tx = await program.methods
+      .depositFromNative(amount, new anchor.BN(0))
+      .accounts({
+        userSigner,
+        userWrappedAccount,
+        userLpTokenAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultInputTokenAccount,
+        daoTreasuryLpTokenAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  });
+
+  it("Open a withdraw ticket in the first epoch", async () => {
+    const lpAmount = (await tokenAmount(userLpTokenAccount)).divn(2);
+    await openWithdrawTicket(lpAmount, 0);
+
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      withdrawTicketAccount
+    );
+    assert.strictEqual(ticketData.epoch.toNumber(), 0);
+    assert.isTrue(ticketData.lpAmount.eq(lpAmount));
+    assert.isTrue(ticketData.openedAt.gtn(0));
+
+    const epochData = await program.account.withdrawEpochAccount.fetch(
+      await withdrawEpochAddress(0)
+    );
+    assert.isTrue(epochData.requestedLp.eq(lpAmount));
+    assert.isTrue(epochData.requestedAmount.eq(ticketData.amount));
+    assert.isTrue((await tokenAmount(vaultUserTicketAccount)).eq(lpAmount));
+  });
+
+  it("Partially fill the epoch and pay the ticket pro-rata", async () => {
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      withdrawTicketAccount
+    );
+    const halfAmount = ticketData.amount.divn(2);
+
+    await fillWithdrawEpoch(halfAmount, 0);
+
+    const vaultData = await program.decodeVault();
+    assert.strictEqual(vaultData.withdrawEpoch.toNumber(), 1);
+    assert.strictEqual(vaultData.pendingWithdrawEpoch.toNumber(), 0);
+    assert.isTrue(vaultData.reservedForTickets.eq(halfAmount));
+
+    const balanceBefore = await tokenAmount(userWrappedAccount);
+    await closeWithdrawTicket(0);
+    const balanceAfter = await tokenAmount(userWrappedAccount);
+    assert.isTrue(balanceAfter.sub(balanceBefore).eq(halfAmount));
+
+    // The ticket tokens are kept until the epoch is completely filled
+    assert.isTrue((await tokenAmount(vaultUserTicketAccount)).gtn(0));
+  });
+
+  it("Tickets of a previous epoch must be closed before opening new ones", async () => {
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      withdrawTicketAccount
+    );
+    const lpAmount = await tokenAmount(userLpTokenAccount);
+
+    await assertProgramError(
+      program.provider.sendAndConfirm(
+        await openWithdrawTicketTx(lpAmount, 1),
+        [],
+        CONFIRM_OPTS_PREFLIGHT
+      ),
+      "TicketInPreviousEpoch"
+    );
+
+    assert.strictEqual(ticketData.epoch.toNumber(), 0);
+  });
+
+  it("Set the keeper settle bounty", async () => {
+    const tx = await program.methods
+      .setSettleBounty(SETTLE_BOUNTY_BPS)
+      .accounts({
+        userSigner,
+        configAccount,
+        vaultAccount,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const vaultData = await program.decodeVault();
+    assert.strictEqual(vaultData.settleBountyBps, SETTLE_BOUNTY_BPS);
+  });
+
+  it("Fill the rest of the epoch and let a keeper settle the ticket", async () => {
+    const epochData = await program.account.withdrawEpochAccount.fetch(
+      await withdrawEpochAddress(0)
+    );
+    const outstanding = epochData.owedAmount.sub(epochData.filledAmount);
+
+    await fillWithdrawEpoch(outstanding, 0);
+
+    const vaultData = await program.decodeVault();
+    assert.strictEqual(vaultData.pendingWithdrawEpoch.toNumber(), 1);
+
+    const userInputTokenAccount = await spl.getAssociatedTokenAddress(
+      INPUT_TOKEN_MINT,
+      userSigner,
+      false
+    );
+    const tx = new anchor.web3.Transaction()
+      .add(
+        spl.createAssociatedTokenAccountInstruction(
+          userSigner,
+          userInputTokenAccount,
+          userSigner,
+          INPUT_TOKEN_MINT
+        )
+      )
+      .add(
+        await program.methods
+          .settleWithdrawTicket(bumpUser)
+          .accounts({
+            keeperSigner: userSigner,
+            keeperInputTokenAccount: userWrappedAccount,
+            user: userSigner,
+            userInputTokenAccount,
+            vaultUserTicketAccount,
+            vaultAccount,
+            vaultTicketMintPubkey,
+            vaultInputTokenAccount,
+            withdrawTicketAccount,
+            withdrawEpochAccount: await withdrawEpochAddress(0),
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+          })
+          .transaction()
+      );
+    const keeperBalanceBefore = await tokenAmount(userWrappedAccount);
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+    const keeperBalanceAfter = await tokenAmount(userWrappedAccount);
+
+    const userBalance = await tokenAmount(userInputTokenAccount);
+    const bounty = keeperBalanceAfter.sub(keeperBalanceBefore);
+    assert.isTrue(bounty.gtn(0));
+    assert.isTrue(
+      bounty.eq(
+        userBalance.add(bounty).muln(SETTLE_BOUNTY_BPS).divn(WEIGHTS_SCALE)
+      )
+    );
+
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      withdrawTicketAccount
+    );
+    assert.isTrue(ticketData.lpAmount.isZero());
+    assert.isTrue((await tokenAmount(vaultUserTicketAccount)).isZero());
+
+    // The reserved funds have been paid or returned to the vault
+    const vaultDataAfter = await program.decodeVault();
+    assert.isTrue(vaultDataAfter.reservedForTickets.isZero());
+  });
+
+  it("Open a new ticket in the next epoch", async () => {
+    const lpAmount = await tokenAmount(userLpTokenAccount);
+    await openWithdrawTicket(lpAmount, 1);
+
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      withdrawTicketAccount
+    );
+    assert.strictEqual(ticketData.epoch.toNumber(), 1);
+  });
+
+  it("Only the strategist can close the epoch", async () => {
+    const keeper = anchor.web3.Keypair.generate();
+    try {
+      const tx = await program.methods
+        .fillWithdrawEpoch(new anchor.BN(0))
+        .accounts({
+          userSigner: keeper.publicKey,
+          configAccount,
+          vaultAccount,
+          withdrawEpochAccount: await withdrawEpochAddress(1),
+          vaultLpTokenMintPubkey,
+          vaultInputTokenAccount,
+          vaultLpTokenAccount,
+          tokenProgram: spl.TOKEN_PROGRAM_ID,
+        })
+        .transaction();
+      await program.provider.sendAndConfirm(tx, [keeper], CONFIRM_OPTS);
+      assert.fail("The epoch should only be closed by the strategist");
+    } catch (err) {
+      assert.notInclude(
+        err.toString(),
+        "The epoch should only be closed by the strategist"
+      );
+    }
+
+    const epochData = await program.account.withdrawEpochAccount.fetch(
+      await withdrawEpochAddress(1)
+    );
+    assert.isTrue(epochData.closedAt.isZero());
+  });
+
+  it("Cancel the ticket while its epoch is open", async () => {
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      withdrawTicketAccount
+    );
+    const lpBalanceBefore = await tokenAmount(userLpTokenAccount);
+
+    const tx = await program.methods
+      .cancelWithdrawTicket(bumpUser, ticketData.lpAmount)
+      .accounts({
+        userSigner,
+        userLpTokenAccount,
+        vaultUserTicketAccount,
+        vaultAccount,
+        vaultLpTokenMintPubkey,
+        vaultTicketMintPubkey,
+        vaultLpTokenAccount,
+        withdrawTicketAccount,
+        withdrawEpochAccount: await withdrawEpochAddress(1),
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const lpBalanceAfter = await tokenAmount(userLpTokenAccount);
+    assert.isTrue(lpBalanceAfter.sub(lpBalanceBefore).eq(ticketData.lpAmount));
+    assert.isTrue((await tokenAmount(vaultUserTicketAccount)).isZero());
+
+    const epochData = await program.account.withdrawEpochAccount.fetch(
+      await withdrawEpochAddress(1)
+    );
+    assert.isTrue(epochData.requestedLp.isZero());
+    assert.isTrue(epochData.requestedAmount.isZero());
+  });
+
+  it("Close the empty ticket accounts", async () => {
+    const tx = await program.methods
+      .closeWithdrawTicketAccounts(bumpUser)
+      .accounts({
+        userSigner,
+        vaultUserTicketAccount,
+        vaultAccount,
+        vaultTicketMintPubkey,
+        withdrawTicketAccount,
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const [ticketInfo, vaultUserTicketInfo] = await Promise.all([
+      program.provider.connection.getAccountInfo(withdrawTicketAccount),
+      program.provider.connection.getAccountInfo(vaultUserTicketAccount),
+    ]);
+    assert.isNull(ticketInfo);
+    assert.isNull(vaultUserTicketInfo);
+  });
+});
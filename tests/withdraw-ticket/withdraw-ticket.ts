@@ -1,7 +1,7 @@
 import * as anchor from "@project-serum/anchor";
 import * as spl from "@solana/spl-token";
 import { assert } from "chai";
-import { GoblinGold, Protocols, TOKENS, decodeAccount } from "goblin-sdk";
+import { GoblinGold, Protocols, TOKENS, decodeAccount } from "goblin-sdk-local";
 
 const INPUT_TOKEN = "WSOL";
 const INPUT_TOKEN_MINT = new anchor.web3.PublicKey(
@@ -34,16 +34,76 @@ describe("withdraw-ticket", () => {
 
   program.setToken(INPUT_TOKEN);
 
+  let vaultUserTicketAccount: anchor.web3.PublicKey;
+  let bumpUser: number;
+
+  async function withdrawTicketAddress() {
+    const [withdrawTicketAccount] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from("withdraw_ticket"),
+          program.vaultKeys[INPUT_TOKEN].vaultAccount.toBuffer(),
+          userSigner.toBuffer(),
+        ],
+        program.programId
+      );
+    return withdrawTicketAccount;
+  }
+
+  async function withdrawEpochAddress(epoch: number) {
+    const [withdrawEpochAccount] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from("withdraw_epoch"),
+          program.vaultKeys[INPUT_TOKEN].vaultAccount.toBuffer(),
+          new anchor.BN(epoch).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+    return withdrawEpochAccount;
+  }
+
+  it("Initialize config", async () => {
+    const [configAccount, _bump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("config")],
+        program.programId
+      );
+
+    const tx = await program.methods
+      .initializeConfig()
+      .accounts({
+        userSigner,
+        configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+  });
+
   it("Initialize vault with weights", async () => {
     const tx = await program.initializeVault(new anchor.BN(0));
 
+    const [configAccount, _configBump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("config")],
+        program.programId
+      );
+    const [registryAccount, _registryBump] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("registry")],
+        program.programId
+      );
+
     const txProtocols = await Promise.all(
       PROTOCOLS.map(async (protocol) =>
         program.methods
           .addProtocol(protocol)
           .accounts({
             userSigner,
+            configAccount,
             vaultAccount: program.vaultKeys[INPUT_TOKEN].vaultAccount,
+            registryAccount,
           })
           .transaction()
       )
@@ -63,6 +123,16 @@ describe("withdraw-ticket", () => {
   it("Initialize withdraw ticket mint", async () => {
     const tx = await program.initializeTicketMint();
     await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    [vaultUserTicketAccount, bumpUser] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from("ticket_mint"),
+          program.vaultKeys[INPUT_TOKEN].vaultTicketMintPubkey.toBuffer(),
+          userSigner.toBuffer(),
+        ],
+        program.programId
+      );
   });
 
   it("Initialize protocol accounts", async () => {
@@ -154,74 +224,167 @@ describe("withdraw-ticket", () => {
   });
 
   it("Open withdraw ticket", async () => {
+    const { vaultAccount, vaultLpTokenMintAddress, vaultTicketMintPubkey } =
+      program.vaultKeys[INPUT_TOKEN];
+
     const userLpTokenAccount = await spl.getAssociatedTokenAddress(
-      program.vaultKeys[INPUT_TOKEN].vaultLpTokenMintAddress,
+      vaultLpTokenMintAddress,
       userSigner,
       false
     );
+    const vaultLpTokenAccount = await spl.getAssociatedTokenAddress(
+      vaultLpTokenMintAddress,
+      vaultAccount,
+      true
+    );
+    const vaultData = await program.decodeVault();
 
-    const [vaultUserTicketAccount, _bump] =
-      await anchor.web3.PublicKey.findProgramAddress(
-        [
-          Buffer.from("ticket_mint"),
-          program.vaultKeys[INPUT_TOKEN].vaultTicketMintPubkey.toBuffer(),
-          userSigner.toBuffer(),
-        ],
-        program.programId
-      );
-
-    const [userLpTokenAccountInfo, vaultUserTicketAccountInfo] =
-      await anchor.utils.rpc.getMultipleAccounts(program.provider.connection, [
-        userLpTokenAccount,
-        vaultUserTicketAccount,
-      ]);
+    const [
+      userLpTokenAccountInfo,
+      vaultUserTicketAccountInfo,
+      vaultLpTokenAccountInfo,
+    ] = await anchor.utils.rpc.getMultipleAccounts(
+      program.provider.connection,
+      [userLpTokenAccount, vaultUserTicketAccount, vaultLpTokenAccount]
+    );
 
     if (!userLpTokenAccountInfo) {
       throw new Error("Error: user_lp_token_account not found");
     }
 
+    // Withdraw half of the position, which can be filled with the idle funds
     const data = decodeAccount(userLpTokenAccountInfo.account.data);
-    const lpAmount = new anchor.BN(data.amount);
+    const lpAmount = new anchor.BN(data.amount).divn(2);
 
     const tx = new anchor.web3.Transaction();
 
+    if (!vaultLpTokenAccountInfo) {
+      tx.add(
+        spl.createAssociatedTokenAccountInstruction(
+          userSigner,
+          vaultLpTokenAccount,
+          vaultAccount,
+          vaultLpTokenMintAddress
+        )
+      );
+    }
+
     if (!vaultUserTicketAccountInfo) {
       tx.add(
-        await program.createVaultUserTicketAccount({
-          userSigner: userSigner,
-          userTicketAccountOwner: userSigner,
-        })
+        await program.methods
+          .createVaultUserTicketAccount()
+          .accounts({
+            userSigner,
+            userTicketAccountOwner: userSigner,
+            vaultUserTicketAccount,
+            vaultAccount,
+            vaultTicketMintPubkey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          })
+          .transaction()
       );
     }
 
     tx.add(
-      await program.openWithdrawTicket({
-        userLpTokenAccount,
-        lpAmount,
+      await program.methods
+        .openWithdrawTicket(bumpUser, lpAmount)
+        .accounts({
+          userSigner,
+          userLpTokenAccount,
+          vaultUserTicketAccount,
+          vaultAccount,
+          vaultLpTokenMintPubkey: vaultLpTokenMintAddress,
+          vaultTicketMintPubkey,
+          vaultLpTokenAccount,
+          daoTreasuryLpTokenAccount: vaultData.daoTreasuryLpTokenAccount,
+          withdrawTicketAccount: await withdrawTicketAddress(),
+          withdrawEpochAccount: await withdrawEpochAddress(0),
+          tokenProgram: spl.TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .transaction()
+    );
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      await withdrawTicketAddress()
+    );
+    assert.isTrue(ticketData.lpAmount.eq(lpAmount));
+  });
+
+  it("Withdraw from the protocols", async () => {
+    const [configAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("config")],
+      program.programId
+    );
+    const tx = await program.methods
+      .setProtocolWeights(PROTOCOLS.map(() => 0))
+      .accounts({
+        userSigner,
+        configAccount,
+        vaultAccount: program.vaultKeys[INPUT_TOKEN].vaultAccount,
       })
+      .transaction();
+    await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const [txsWithdraw, _txsDeposit] = await program.rebalance();
+    await Promise.all(
+      txsWithdraw.map(async (tx) =>
+        program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS)
+      )
     );
+  });
+
+  it("Fill withdraw epoch", async () => {
+    const { vaultAccount, vaultLpTokenMintAddress } =
+      program.vaultKeys[INPUT_TOKEN];
+    const [configAccount] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from("config")],
+      program.programId
+    );
+
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      await withdrawTicketAddress()
+    );
+
+    const tx = await program.methods
+      .fillWithdrawEpoch(ticketData.amount)
+      .accounts({
+        userSigner,
+        configAccount,
+        vaultAccount,
+        withdrawEpochAccount: await withdrawEpochAddress(0),
+        vaultLpTokenMintPubkey: vaultLpTokenMintAddress,
+        vaultInputTokenAccount: await spl.getAssociatedTokenAddress(
+          INPUT_TOKEN_MINT,
+          vaultAccount,
+          true
+        ),
+        vaultLpTokenAccount: await spl.getAssociatedTokenAddress(
+          vaultLpTokenMintAddress,
+          vaultAccount,
+          true
+        ),
+        tokenProgram: spl.TOKEN_PROGRAM_ID,
+      })
+      .transaction();
     await program.provider.sendAndConfirm(tx, [], CONFIRM_OPTS);
+
+    const epochData = await program.account.withdrawEpochAccount.fetch(
+      await withdrawEpochAddress(0)
+    );
+    assert.isTrue(epochData.filledAmount.eq(epochData.owedAmount));
   });
 
   it("Close withdraw ticket", async () => {
-    const [vaultUserTicketAccount, _bump] =
-      await anchor.web3.PublicKey.findProgramAddress(
-        [
-          Buffer.from("ticket_mint"),
-          program.vaultKeys[INPUT_TOKEN].vaultTicketMintPubkey.toBuffer(),
-          userSigner.toBuffer(),
-        ],
-        program.programId
-      );
+    const { vaultAccount, vaultTicketMintPubkey } =
+      program.vaultKeys[INPUT_TOKEN];
 
-    const vaultUserTicketAccountInfo =
-      await program.provider.connection.getAccountInfo(vaultUserTicketAccount);
-    if (!vaultUserTicketAccountInfo) {
-      throw new Error("Error: vault_user_ticket_account_info not found");
-    }
-
-    const data = decodeAccount(vaultUserTicketAccountInfo.data);
-    const lpAmount = new anchor.BN(data.amount);
+    const epochData = await program.account.withdrawEpochAccount.fetch(
+      await withdrawEpochAddress(0)
+    );
 
     const wrappedKeypair = anchor.web3.Keypair.generate();
     const userInputTokenAccount = wrappedKeypair.publicKey;
@@ -229,44 +392,59 @@ describe("withdraw-ticket", () => {
       program.provider.connection
     );
 
-    const txs = await program.closeWithdrawTicket({
-      userInputTokenAccount,
-      lpAmount,
-    });
+    const tx = new anchor.web3.Transaction()
+      .add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: userSigner,
+          newAccountPubkey: userInputTokenAccount,
+          space: spl.ACCOUNT_SIZE,
+          lamports,
+          programId: spl.TOKEN_PROGRAM_ID,
+        }),
+        spl.createInitializeAccountInstruction(
+          userInputTokenAccount,
+          spl.NATIVE_MINT,
+          userSigner
+        )
+      )
+      .add(
+        await program.methods
+          .closeWithdrawTicket(bumpUser, epochData.owedAmount)
+          .accounts({
+            userSigner,
+            userInputTokenAccount,
+            vaultUserTicketAccount,
+            vaultAccount,
+            vaultTicketMintPubkey,
+            vaultInputTokenAccount: await spl.getAssociatedTokenAddress(
+              INPUT_TOKEN_MINT,
+              vaultAccount,
+              true
+            ),
+            withdrawTicketAccount: await withdrawTicketAddress(),
+            withdrawEpochAccount: await withdrawEpochAddress(0),
+            tokenProgram: spl.TOKEN_PROGRAM_ID,
+          })
+          .transaction()
+      )
+      .add(
+        spl.createCloseAccountInstruction(
+          userInputTokenAccount,
+          userSigner,
+          userSigner,
+          []
+        )
+      );
+    await program.provider.sendAndConfirm(tx, [wrappedKeypair], CONFIRM_OPTS);
 
-    await Promise.all(
-      txs.map(async (tx) => {
-        const txAll = new anchor.web3.Transaction()
-          .add(
-            anchor.web3.SystemProgram.createAccount({
-              fromPubkey: userSigner,
-              newAccountPubkey: userInputTokenAccount,
-              space: spl.ACCOUNT_SIZE,
-              lamports,
-              programId: spl.TOKEN_PROGRAM_ID,
-            }),
-            spl.createInitializeAccountInstruction(
-              userInputTokenAccount,
-              spl.NATIVE_MINT,
-              userSigner
-            )
-          )
-          .add(tx)
-          .add(
-            spl.createCloseAccountInstruction(
-              userInputTokenAccount,
-              userSigner,
-              userSigner,
-              []
-            )
-          );
-
-        return program.provider.sendAndConfirm(
-          txAll,
-          [wrappedKeypair],
-          CONFIRM_OPTS
-        );
-      })
+    const ticketData = await program.account.withdrawTicketAccount.fetch(
+      await withdrawTicketAddress()
     );
+    assert.isTrue(ticketData.lpAmount.isZero());
+
+    const vaultUserTicketAccountInfo =
+      await program.provider.connection.getAccountInfo(vaultUserTicketAccount);
+    const data = decodeAccount(vaultUserTicketAccountInfo.data);
+    assert.isTrue(new anchor.BN(data.amount).isZero());
   });
 });